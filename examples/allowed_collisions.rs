@@ -0,0 +1,37 @@
+//! Generates the allowed-collision matrices shipped with the CRX mesh features by sampling
+//! random joint angles. Run with:
+//!
+//! ```text
+//! cargo run --release --example allowed_collisions --features mesh_fanuc_crx
+//! ```
+
+use industrial_robots::fanuc::{Crx, crx5ia_mesh, crx10ia_mesh};
+use industrial_robots::{AllowedCollisions, Point3, Result, TriMesh};
+
+const SAMPLES: usize = 200_000;
+const SEED: u64 = 1;
+
+fn to_trimeshes(meshes: Vec<(Vec<Point3>, Vec<[u32; 3]>)>) -> Result<Vec<TriMesh>> {
+    meshes
+        .into_iter()
+        .map(|(vertices, triangles)| Ok(TriMesh::new(vertices, triangles)?))
+        .collect()
+}
+
+fn main() -> Result<()> {
+    let models = [
+        ("crx5ia", Crx::new_5ia(), crx5ia_mesh()),
+        ("crx10ia", Crx::new_10ia(), crx10ia_mesh()),
+    ];
+
+    for (name, robot, meshes) in models {
+        let matrix = AllowedCollisions::sample(&robot, &to_trimeshes(meshes)?, SAMPLES, SEED)?;
+        println!("{} ({} samples, seed {}):", name, SAMPLES, SEED);
+        for (i, j, contact) in matrix.allowed_pairs() {
+            println!("    ({}, {}, LinkContact::{:?}),", i, j, contact);
+        }
+        println!("  checked: {:?}", matrix.checked_pairs());
+    }
+
+    Ok(())
+}
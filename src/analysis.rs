@@ -0,0 +1,226 @@
+//! Tools for analyzing how well conditioned a robot's pose is. Near a singularity the robot loses
+//! the ability to move the flange in some direction, and small Cartesian motions require very
+//! large joint motions, which is usually only discovered when the real robot faults or slows
+//! down. These metrics allow cell layouts and paths to be checked ahead of time.
+
+use crate::Robot;
+
+/// The different kinds of singularity a 6-axis arm can approach
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SingularityKind {
+    /// The wrist joints (J4, J5, J6) can move without moving the flange, which for most arms
+    /// means J5 is at zero and J4 and J6 are aligned
+    Wrist,
+
+    /// The upper arm and forearm become collinear, with the arm either fully stretched out or
+    /// fully folded back on itself
+    Elbow,
+
+    /// The wrist passes through the J1 axis, so rotating J1 no longer moves it
+    Shoulder,
+}
+
+/// The result of analyzing a robot at a single set of joint angles
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PoseAnalysis {
+    /// The Yoshikawa manipulability measure, `sqrt(det(J * J^T))`. This is zero at a singularity
+    /// and grows as the robot becomes more dexterous.
+    pub manipulability: f64,
+
+    /// The ratio of the largest to the smallest singular value of the Jacobian. This is one for
+    /// a perfectly isotropic pose and goes to infinity at a singularity.
+    pub condition: f64,
+
+    /// The smallest singular value of the Jacobian, which is how far the pose is from the
+    /// nearest singularity. It is zero exactly when the Jacobian loses rank.
+    pub min_singular_value: f64,
+
+    /// The unit joint motion (in radians, in the controller convention) which moves the flange
+    /// the least, which is the right singular vector of the smallest singular value. At a
+    /// singularity this motion doesn't move the flange at all.
+    pub null_motion: [f64; 6],
+
+    /// The kind of singularity the pose is closest to, found from which joints make up the
+    /// `null_motion`
+    pub nearest: SingularityKind,
+}
+
+/// Analyze a robot's manipulability and singularities at a set of joint angles.
+///
+/// Everything is computed from the geometric Jacobian with its linear rows divided by the
+/// length of the arm, which makes it dimensionless and balances the millimeters of the linear
+/// rows against the radians of the angular rows. This works the same for arms with a spherical
+/// wrist and arms with an offset wrist (like the CRX), whose singular poses are more varied than
+/// the classical wrist, elbow, and shoulder configurations.
+///
+/// The kind of the nearest singularity comes from the joint motion which moves the flange the
+/// least. When more than three quarters of that motion (by its squared norm) is in the wrist
+/// joints, the pose is closest to a wrist singularity. Otherwise it is a shoulder singularity if
+/// J1 moves more than J2 and J3 together, and an elbow singularity if not.
+///
+/// The robot's `fk_all` chain is expected to follow the layout of the arms in this crate, where
+/// each frame is located on the axis of its joint. The length of the arm is taken from the
+/// origin of the J2 frame to the origin of the J3 frame, and from there to the closest point on
+/// the J4 axis to the origin of the J5 frame, which is the wrist center on arms with a spherical
+/// wrist.
+///
+/// # Arguments
+///
+/// * `robot`: the robot to analyze
+/// * `joints`: the joint angles in degrees, in the controller convention
+///
+/// returns: PoseAnalysis
+///
+/// # Examples
+///
+/// ```
+/// use industrial_robots::analysis::{analyze, SingularityKind};
+/// use industrial_robots::fanuc::Crx;
+///
+/// // With J4 and J5 at zero, J4 and J6 can turn against each other (with a little J3 and J5)
+/// // without moving the flange
+/// let robot = Crx::new_5ia();
+/// let result = analyze(&robot, &[0.0, 10.0, -20.0, 0.0, 0.0, 0.0]);
+/// assert_eq!(result.nearest, SingularityKind::Wrist);
+/// assert!(result.min_singular_value < 1e-9);
+/// ```
+pub fn analyze<R: Robot + ?Sized>(robot: &R, joints: &[f64; 6]) -> PoseAnalysis {
+    let chain = robot.fk_all(joints);
+    let jac = robot.jacobian(joints);
+
+    let shoulder_pt = chain[1].translation.vector;
+    let elbow_pt = chain[2].translation.vector;
+    let j4_axis = jac.fixed_view::<3, 1>(3, 3).into_owned();
+    let j4_origin = chain[3].translation.vector;
+    let wrist_pt = j4_origin + j4_axis * j4_axis.dot(&(chain[4].translation.vector - j4_origin));
+    let length = (elbow_pt - shoulder_pt).norm() + (wrist_pt - elbow_pt).norm();
+
+    let mut scaled = jac;
+    scaled.fixed_view_mut::<3, 6>(0, 0).unscale_mut(length);
+    let svd = scaled.svd(false, true);
+    let values = svd.singular_values;
+    let (weakest, min_value) = values
+        .iter()
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(i, v)| (i, *v))
+        .unwrap();
+    let null_motion = svd.v_t.unwrap().row(weakest).transpose();
+
+    let arm = null_motion.fixed_rows::<2>(1).norm();
+    let wrist = null_motion.fixed_rows::<3>(3).norm_squared();
+    let nearest = if wrist > 0.75 {
+        SingularityKind::Wrist
+    } else if null_motion[0].abs() > arm {
+        SingularityKind::Shoulder
+    } else {
+        SingularityKind::Elbow
+    };
+
+    let condition = if min_value > 0.0 {
+        values.max() / min_value
+    } else {
+        f64::INFINITY
+    };
+
+    PoseAnalysis {
+        manipulability: values.product(),
+        condition,
+        min_singular_value: min_value,
+        null_motion: null_motion.into(),
+        nearest,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Frame3;
+    use crate::fanuc::{Crx, LrMate};
+    use crate::nalgebra::{Translation3, UnitQuaternion};
+
+    #[test]
+    fn crx_wrist_singularity() {
+        let robot = Crx::new_5ia();
+        let result = analyze(&robot, &[0.0, 10.0, -20.0, 0.0, 0.0, 0.0]);
+
+        assert_eq!(result.nearest, SingularityKind::Wrist);
+        assert!(result.min_singular_value < 1e-9);
+        assert!(result.manipulability < 1e-9);
+    }
+
+    #[test]
+    fn crx_j5_zero_not_singular() {
+        // The offset wrist of the CRX keeps J4 and J6 apart when J5 is zero, so this pose still
+        // has full rank even though the wrist axes are coplanar
+        let robot = Crx::new_5ia();
+        let result = analyze(&robot, &[10.0, 20.0, -30.0, 40.0, 0.0, 60.0]);
+
+        assert!(result.min_singular_value > 1e-3);
+        assert!(result.condition < 1e3);
+    }
+
+    #[test]
+    fn crx_shoulder_singularity() {
+        // The forearm is vertical, which puts the wrist point on the J1 axis
+        let robot = Crx::new_5ia();
+        let result = analyze(&robot, &[0.0, 0.0, 90.0, 0.0, 30.0, 0.0]);
+
+        assert_eq!(result.nearest, SingularityKind::Shoulder);
+        assert!(result.min_singular_value < 1e-9);
+        assert!(result.manipulability < 1e-6);
+    }
+
+    #[test]
+    fn crx_elbow_singularity() {
+        // With J2 at zero the upper arm is vertical, so a vertical forearm lines them up. J1 is
+        // turned so that the wrist point is not also on the J1 axis.
+        let robot = Crx::new_5ia();
+        let result = analyze(&robot, &[0.0, 30.0, 60.0, 0.0, 30.0, 0.0]);
+
+        assert_eq!(result.nearest, SingularityKind::Elbow);
+        assert!(result.min_singular_value < 1e-9);
+        assert!(result.manipulability < 1e-6);
+    }
+
+    #[test]
+    fn lr_mate_wrist_singularity() {
+        let robot = LrMate::new_200id();
+        let result = analyze(&robot, &[10.0, 20.0, -30.0, 40.0, 0.0, 60.0]);
+
+        assert_eq!(result.nearest, SingularityKind::Wrist);
+        assert!(result.manipulability < 1e-12);
+        assert!(result.condition > 1e12);
+
+        // J4 and J6 turning against each other
+        let motion = result.null_motion;
+        assert!((motion[3] + motion[5]).abs() < 1e-9 && motion[3].abs() > 0.7);
+    }
+
+    #[test]
+    fn independent_of_base() {
+        let base = Frame3::from_parts(
+            Translation3::new(800.0, -300.0, 1200.0),
+            UnitQuaternion::from_euler_angles(0.4, -0.6, 1.2),
+        );
+        let joints = [10.0, 20.0, -30.0, 40.0, 50.0, 60.0];
+        let placed = analyze(&Crx::new_5ia().with_base(base), &joints);
+        let nominal = analyze(&Crx::new_5ia(), &joints);
+
+        assert_eq!(placed.nearest, nominal.nearest);
+        assert!((placed.manipulability - nominal.manipulability).abs() < 1e-9);
+        assert!((placed.min_singular_value - nominal.min_singular_value).abs() < 1e-9);
+    }
+
+    #[test]
+    fn well_conditioned_pose() {
+        let robot = LrMate::new_200id();
+        let good = analyze(&robot, &[0.0, 10.0, -20.0, 30.0, -60.0, 0.0]);
+        let near = analyze(&robot, &[0.0, 10.0, -20.0, 30.0, -0.5, 0.0]);
+
+        assert!(good.manipulability > near.manipulability);
+        assert!(good.condition < near.condition);
+        assert!(good.min_singular_value > 0.1 && near.min_singular_value < 0.01);
+        assert_eq!(near.nearest, SingularityKind::Wrist);
+    }
+}
@@ -1,6 +1,10 @@
 //! This module contains tools for detecting collisions between objects in a 3D space.
 
 use crate::{Frame3, Result};
+
+#[cfg(feature = "serde")]
+use crate::{Point3, XyzWpr};
+use parry3d_f64::bounding_volume::BoundingVolume;
 use parry3d_f64::query;
 use parry3d_f64::query::intersection_test;
 use rayon::prelude::*;
@@ -14,24 +18,66 @@ use parry3d_f64::utils::hashset::HashSet;
 
 // Re-export the TriMesh type from parry3d_f64
 pub use parry3d_f64::shape::TriMesh;
+use parry3d_f64::shape::{Shape, SharedShape};
+
+mod allowed_collisions;
+mod posed_robot;
+
+pub use allowed_collisions::{AllowedCollisions, LinkContact};
+pub(crate) use posed_robot::link_transforms;
+pub use posed_robot::{PosedRobot, SweptContact};
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 enum MeshType {
     Background,
     Interest,
 }
 
 struct MeshItem {
-    shape: TriMesh,
+    shape: SharedShape,
     kind: MeshType,
 }
 
+/// A set of meshes which can be checked for collisions against each other.
+///
+/// Any `parry3d_f64` shape can be added to a scene, not just a `TriMesh`. Fixtures, fences, and
+/// tables are usually better described by a `Cuboid`, `Cylinder`, `Capsule`, `ConvexPolyhedron`,
+/// or a `Compound` of them, which are both faster to check and solid. A `TriMesh` is only its
+/// surface, so a shape entirely inside a triangle mesh doesn't collide with it, while a shape
+/// entirely inside a primitive does.
+///
+/// With the `serde` feature a scene is serialized with each mesh's id, kind (`"background"` or
+/// `"interest"`), and shape, along with the excluded pairs of ids. Triangle meshes are stored
+/// as their vertices and triangle indices, and the other shapes have a `"shape"` tag with their
+/// dimensions. Mesh ids are kept when the scene is loaded, so the ids in a saved job remain
+/// valid.
+///
+/// ```json
+/// {
+///   "meshes": [
+///     { "id": 0, "kind": "background", "vertices": [[0.0, 0.0, 0.0], ...], "triangles": [[0, 1, 2], ...] },
+///     { "id": 1, "kind": "background", "shape": "cuboid", "half_extents": [500.0, 400.0, 10.0] }
+///   ],
+///   "exclusions": [[0, 1]]
+/// }
+/// ```
 pub struct CollisionScene {
     meshes: HashMap<usize, MeshItem>,
     exclusions: HashSet<(usize, usize)>,
     next_id: usize,
 }
 
+impl Default for CollisionScene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CollisionScene {
     pub fn new() -> Self {
         Self {
@@ -45,12 +91,20 @@ impl CollisionScene {
         self.meshes.len()
     }
 
-    pub fn add_background(&mut self, mesh: TriMesh) -> usize {
-        self.add_mesh(mesh, MeshType::Background)
+    pub fn is_empty(&self) -> bool {
+        self.meshes.is_empty()
     }
 
-    pub fn add_interest(&mut self, mesh: TriMesh) -> usize {
-        self.add_mesh(mesh, MeshType::Interest)
+    /// Add a stationary shape to the scene, such as a triangle mesh of a fixture or a `Cuboid`
+    /// for a table, and return its id
+    pub fn add_background(&mut self, shape: impl Shape) -> usize {
+        self.add_mesh(SharedShape::new(shape), MeshType::Background)
+    }
+
+    /// Add a moving shape to the scene, which is checked against every other shape, and return
+    /// its id
+    pub fn add_interest(&mut self, shape: impl Shape) -> usize {
+        self.add_mesh(SharedShape::new(shape), MeshType::Interest)
     }
 
     pub fn remove_mesh(&mut self, id: usize) {
@@ -77,6 +131,8 @@ impl CollisionScene {
     ///   including both stationary and other moving meshes
     /// - Stationary meshes will not be checked against any other meshes, and so a collision will
     ///   only be reported if it is with a stationary mesh
+    /// - Only pairs whose bounding boxes overlap at their transforms go on to the exact check of
+    ///   their shapes, and the moving meshes are checked in parallel
     ///
     /// # Arguments
     ///
@@ -84,7 +140,9 @@ impl CollisionScene {
     /// * `stop_at_first`: If true, the function will stop at the first collision found for each
     ///   moving mesh. If false, it will check all collisions.
     ///
-    /// returns: Vec<(usize, usize), Global>
+    /// returns: Vec<(usize, usize), Global>, sorted, where each pair is the moving mesh followed by
+    /// the mesh it collides with, or an error if a transform is given for an id which isn't in the
+    /// scene
     ///
     /// # Examples
     ///
@@ -97,55 +155,108 @@ impl CollisionScene {
         stop_at_first: bool,
         skip_ids: Option<&[usize]>,
     ) -> Result<Vec<(usize, usize)>> {
+        if let Some((id, _)) = transforms
+            .iter()
+            .find(|(id, _)| !self.meshes.contains_key(id))
+        {
+            return Err(format!("Mesh id {} not found", id).into());
+        }
+
         // Create the fast isometry lookup and fast skip id lookup:
         let lookups = self.quick_lookups(transforms);
         let skip_ids = self.quick_skip_ids(skip_ids);
 
-        // We'll iterate through all the interest meshes (this can be parallelized later).
-        // For each interest mesh, we'll iterate through every background mesh and every interest
-        // mesh with a higher id (to avoid double-checking pairs of interest meshes).
-        //
-        // Whether a pair of meshes is checked depends on the following:
-        // - Is the current mesh id lower than the other mesh id?
-        // - Is there an exception for the current pair of meshes?
-        let mut pairs = Vec::new();
+        // The broad phase finds every pair of meshes whose world space bounding boxes overlap and
+        // which should be checked at all, grouped by the interest mesh that owns the pair. Pairs
+        // between two interest meshes belong to the one with the lower id, so that each pair is
+        // only checked once.
+        let mut candidates = self.broad_phase(&lookups, &skip_ids);
+
+        // The narrow phase runs in parallel over the interest meshes. Each mesh's candidates are
+        // checked in order of id so that `stop_at_first` reports the same pair every time. A pair
+        // of shapes that parry can't check is an error rather than being reported as clear.
+        let found = candidates
+            .par_iter_mut()
+            .map(|(id1, others)| {
+                others.sort_unstable();
+                let mesh1 = &self.meshes[id1];
+                let iso1 = &lookups[id1];
+
+                let mut found = Vec::new();
+                for &id2 in others.iter() {
+                    let mesh2 = &self.meshes[&id2];
+                    let iso2 = &lookups[&id2];
+
+                    let touching =
+                        shapes_touch(iso1, &*mesh1.shape, iso2, &*mesh2.shape).map_err(|e| {
+                            format!("Collision check between {} and {} failed: {}", id1, id2, e)
+                        })?;
+                    if touching {
+                        found.push((*id1, id2));
+                        if stop_at_first {
+                            break;
+                        }
+                    }
+                }
+                Ok(found)
+            })
+            .collect::<std::result::Result<Vec<_>, String>>()?;
+
+        let mut pairs = found.into_iter().flatten().collect::<Vec<_>>();
+        pairs.sort_unstable();
+        Ok(pairs)
+    }
 
-        // Pre-identify the meshes that are not background and not in the skip list
-        let working = self
+    /// Find the pairs of meshes which need to go through the narrow phase of `check_all`, keyed by
+    /// the interest mesh that owns each pair. This is a sweep-and-prune along the x-axis over the
+    /// bounding boxes of the meshes at their transforms, after which the pairs which overlap on
+    /// all three axes are filtered by mesh kind, the skip list, and the exclusions.
+    fn broad_phase(
+        &self,
+        lookups: &HashMap<usize, Frame3>,
+        skip_ids: &HashSet<usize>,
+    ) -> Vec<(usize, Vec<usize>)> {
+        let mut boxes = self
             .meshes
             .iter()
-            .filter(|&(id, mesh)| !skip_ids.contains(id) && mesh.kind != MeshType::Background)
-            .map(|(id, _)| *id)
+            .filter(|(id, _)| !skip_ids.contains(*id))
+            .map(|(&id, mesh)| {
+                (
+                    id,
+                    mesh.kind == MeshType::Interest,
+                    mesh.shape.compute_aabb(&lookups[&id]),
+                )
+            })
             .collect::<Vec<_>>();
-
-        for id1 in working {
-            let mesh1 = self.meshes.get(&id1).unwrap();
-            let iso1 = &lookups[&id1];
-
-            for (&id2, mesh2) in self.meshes.iter() {
-                if mesh2.kind == MeshType::Interest && id1 >= id2 {
-                    continue;
+        boxes.sort_unstable_by(|a, b| a.2.mins.x.total_cmp(&b.2.mins.x));
+
+        let mut candidates: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (i, (id_a, interest_a, box_a)) in boxes.iter().enumerate() {
+            for (id_b, interest_b, box_b) in boxes[i + 1..].iter() {
+                // The boxes are sorted by their lower x bound, so once one starts beyond the end
+                // of this box none of the rest can overlap it either
+                if box_b.mins.x > box_a.maxs.x {
+                    break;
                 }
 
-                if skip_ids.contains(&id2) || self.skip_collision(id1, id2) {
+                if !box_a.intersects(box_b) {
                     continue;
                 }
 
-                let iso2 = &lookups[&id2];
+                let (id1, id2) = match (interest_a, interest_b) {
+                    (true, true) => (*id_a.min(id_b), *id_a.max(id_b)),
+                    (true, false) => (*id_a, *id_b),
+                    (false, true) => (*id_b, *id_a),
+                    (false, false) => continue,
+                };
 
-                // Check for collision
-                if let Ok(check) = intersection_test(iso1, &mesh1.shape, iso2, &mesh2.shape) {
-                    if check {
-                        pairs.push((id1, id2));
-                        if stop_at_first {
-                            break;
-                        }
-                    }
+                if !self.skip_collision(id1, id2) {
+                    candidates.entry(id1).or_default().push(id2);
                 }
             }
         }
 
-        Ok(pairs)
+        candidates.into_iter().collect()
     }
 
     /// Check the distances between a mesh and a sequence of other meshes. Results are returned
@@ -183,7 +294,7 @@ impl CollisionScene {
                 if let Some(mesh2) = self.meshes.get(id) {
                     let iso2 = &lookups[id];
 
-                    if let Ok(d) = query::distance(iso1, &mesh1.shape, iso2, &mesh2.shape) {
+                    if let Ok(d) = query::distance(iso1, &*mesh1.shape, iso2, &*mesh2.shape) {
                         (*id, Ok(d))
                     } else {
                         (*id, Err("Distance check failed"))
@@ -214,7 +325,7 @@ impl CollisionScene {
         //     let iso2 = &lookups[&id2];
         //
         //     // Check for distance
-        //     let d = query::distance(iso1, &mesh1.shape, iso2, &mesh2.shape)
+        //     let d = query::distance(iso1, &*mesh1.shape, iso2, &*mesh2.shape)
         //         .map_err(|e| format!("Distance check to {} failed: {}", id2, e))?;
         //     distances.push(d);
         // }
@@ -235,7 +346,7 @@ impl CollisionScene {
         let mut lookups = HashMap::with_capacity(self.meshes.len());
 
         for &(id, iso) in transforms.iter() {
-            lookups.insert(id, iso.clone());
+            lookups.insert(id, iso);
         }
 
         for id in self.meshes.keys() {
@@ -253,7 +364,7 @@ impl CollisionScene {
         self.exclusions.contains(&(lower, upper))
     }
 
-    fn add_mesh(&mut self, shape: TriMesh, kind: MeshType) -> usize {
+    fn add_mesh(&mut self, shape: SharedShape, kind: MeshType) -> usize {
         let id = self.take_id();
         self.meshes.insert(id, MeshItem { shape, kind });
         id
@@ -266,24 +377,308 @@ impl CollisionScene {
     }
 }
 
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SceneDef {
+    meshes: Vec<MeshDef>,
+
+    #[serde(default)]
+    exclusions: Vec<(usize, usize)>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct MeshDef {
+    id: usize,
+    kind: MeshType,
+
+    #[serde(flatten)]
+    shape: ShapeDef,
+}
+
+/// A triangle mesh is written as its vertices and triangles without a tag, which is the same as
+/// scenes saved before other shapes were supported, while every other shape has a `"shape"` tag.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+enum ShapeDef {
+    Primitive(PrimitiveDef),
+    Mesh {
+        vertices: Vec<[f64; 3]>,
+        triangles: Vec<[u32; 3]>,
+    },
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+enum PrimitiveDef {
+    Cuboid {
+        half_extents: [f64; 3],
+    },
+    Ball {
+        radius: f64,
+    },
+    Capsule {
+        a: [f64; 3],
+        b: [f64; 3],
+        radius: f64,
+    },
+    Cylinder {
+        half_height: f64,
+        radius: f64,
+    },
+    Cone {
+        half_height: f64,
+        radius: f64,
+    },
+    ConvexHull {
+        points: Vec<[f64; 3]>,
+    },
+    Compound {
+        parts: Vec<PartDef>,
+    },
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PartDef {
+    pose: XyzWpr,
+
+    #[serde(flatten)]
+    shape: PrimitiveDef,
+}
+
+#[cfg(feature = "serde")]
+impl ShapeDef {
+    fn from_shape(shape: &dyn Shape) -> Option<Self> {
+        if let Some(mesh) = shape.as_trimesh() {
+            Some(ShapeDef::Mesh {
+                vertices: mesh.vertices().iter().map(|p| [p.x, p.y, p.z]).collect(),
+                triangles: mesh.indices().to_vec(),
+            })
+        } else {
+            PrimitiveDef::from_shape(shape).map(ShapeDef::Primitive)
+        }
+    }
+
+    fn to_shape(&self) -> std::result::Result<SharedShape, String> {
+        match self {
+            ShapeDef::Primitive(primitive) => primitive.to_shape(),
+            ShapeDef::Mesh {
+                vertices,
+                triangles,
+            } => {
+                let vertices = vertices.iter().map(|v| Point3::from(*v)).collect();
+                let mesh = TriMesh::new(vertices, triangles.clone()).map_err(|e| e.to_string())?;
+                Ok(SharedShape::new(mesh))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl PrimitiveDef {
+    fn from_shape(shape: &dyn Shape) -> Option<Self> {
+        let point = |p: &Point3| [p.x, p.y, p.z];
+
+        if let Some(cuboid) = shape.as_cuboid() {
+            let h = cuboid.half_extents;
+            Some(PrimitiveDef::Cuboid {
+                half_extents: [h.x, h.y, h.z],
+            })
+        } else if let Some(ball) = shape.as_ball() {
+            Some(PrimitiveDef::Ball {
+                radius: ball.radius,
+            })
+        } else if let Some(capsule) = shape.as_capsule() {
+            Some(PrimitiveDef::Capsule {
+                a: point(&capsule.segment.a),
+                b: point(&capsule.segment.b),
+                radius: capsule.radius,
+            })
+        } else if let Some(cylinder) = shape.as_cylinder() {
+            Some(PrimitiveDef::Cylinder {
+                half_height: cylinder.half_height,
+                radius: cylinder.radius,
+            })
+        } else if let Some(cone) = shape.as_cone() {
+            Some(PrimitiveDef::Cone {
+                half_height: cone.half_height,
+                radius: cone.radius,
+            })
+        } else if let Some(hull) = shape.as_convex_polyhedron() {
+            Some(PrimitiveDef::ConvexHull {
+                points: hull.points().iter().map(point).collect(),
+            })
+        } else if let Some(compound) = shape.as_compound() {
+            let parts = compound
+                .shapes()
+                .iter()
+                .map(|(pose, part)| {
+                    // parry doesn't build nested compounds, and loading rejects them, so never
+                    // save one
+                    match Self::from_shape(&**part)? {
+                        PrimitiveDef::Compound { .. } => None,
+                        shape => Some(PartDef {
+                            pose: XyzWpr::from_isometry(pose),
+                            shape,
+                        }),
+                    }
+                })
+                .collect::<Option<Vec<_>>>()?;
+            Some(PrimitiveDef::Compound { parts })
+        } else {
+            None
+        }
+    }
+
+    fn to_shape(&self) -> std::result::Result<SharedShape, String> {
+        let shape = match self {
+            PrimitiveDef::Cuboid { half_extents: h } => SharedShape::cuboid(h[0], h[1], h[2]),
+            PrimitiveDef::Ball { radius } => SharedShape::ball(*radius),
+            PrimitiveDef::Capsule { a, b, radius } => {
+                SharedShape::capsule(Point3::from(*a), Point3::from(*b), *radius)
+            }
+            PrimitiveDef::Cylinder {
+                half_height,
+                radius,
+            } => SharedShape::cylinder(*half_height, *radius),
+            PrimitiveDef::Cone {
+                half_height,
+                radius,
+            } => SharedShape::cone(*half_height, *radius),
+            PrimitiveDef::ConvexHull { points } => {
+                let points = points.iter().map(|p| Point3::from(*p)).collect::<Vec<_>>();
+                SharedShape::convex_hull(&points).ok_or("Invalid convex hull")?
+            }
+            PrimitiveDef::Compound { parts } => {
+                if parts.is_empty() {
+                    return Err("A compound shape needs at least one part".to_string());
+                }
+
+                let parts = parts
+                    .iter()
+                    .map(|part| {
+                        if let PrimitiveDef::Compound { .. } = part.shape {
+                            return Err("Compound shapes can't be nested".to_string());
+                        }
+                        Ok((part.pose.to_isometry(), part.shape.to_shape()?))
+                    })
+                    .collect::<std::result::Result<Vec<_>, String>>()?;
+                SharedShape::compound(parts)
+            }
+        };
+
+        Ok(shape)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for CollisionScene {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::Error;
+
+        let mut meshes = self
+            .meshes
+            .iter()
+            .map(|(&id, item)| {
+                let shape = ShapeDef::from_shape(&*item.shape).ok_or_else(|| {
+                    S::Error::custom(format!("Mesh {} has a shape which can't be saved", id))
+                })?;
+                Ok(MeshDef {
+                    id,
+                    kind: item.kind.clone(),
+                    shape,
+                })
+            })
+            .collect::<std::result::Result<Vec<_>, S::Error>>()?;
+        meshes.sort_by_key(|m| m.id);
+
+        let mut exclusions = self.exclusions.iter().copied().collect::<Vec<_>>();
+        exclusions.sort();
+
+        SceneDef { meshes, exclusions }.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for CollisionScene {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let def = SceneDef::deserialize(deserializer)?;
+        let mut scene = CollisionScene::new();
+        for mesh in def.meshes {
+            if scene.meshes.contains_key(&mesh.id) {
+                return Err(D::Error::custom(format!("Duplicate mesh id {}", mesh.id)));
+            }
+
+            let shape = mesh
+                .shape
+                .to_shape()
+                .map_err(|e| D::Error::custom(format!("Invalid mesh {}: {}", mesh.id, e)))?;
+            scene.meshes.insert(
+                mesh.id,
+                MeshItem {
+                    shape,
+                    kind: mesh.kind,
+                },
+            );
+            scene.next_id = scene.next_id.max(mesh.id + 1);
+        }
+
+        for (id1, id2) in def.exclusions {
+            scene.add_exclusion(id1, id2);
+        }
+
+        Ok(scene)
+    }
+}
+
+/// Check if two shapes touch or overlap. In parry 0.19, `intersection_test` misses some exactly
+/// aligned overlaps between primitives, such as a capsule standing on the center of a cuboid, so
+/// a pair without a triangle mesh that it reports as clear is confirmed with the distance between
+/// the shapes, which is zero when they overlap.
+pub(crate) fn shapes_touch(
+    iso1: &Frame3,
+    shape1: &dyn Shape,
+    iso2: &Frame3,
+    shape2: &dyn Shape,
+) -> std::result::Result<bool, query::Unsupported> {
+    if intersection_test(iso1, shape1, iso2, shape2)? {
+        return Ok(true);
+    }
+    if shape1.as_trimesh().is_some() || shape2.as_trimesh().is_some() {
+        return Ok(false);
+    }
+    Ok(query::distance(iso1, shape1, iso2, shape2)? <= 0.0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::Point3;
-    use parry3d_f64::bounding_volume::Aabb;
+    use crate::Vector3;
+    use crate::test_helpers::box_mesh;
     use parry3d_f64::shape::TriMesh;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
 
-    fn box_mesh() -> TriMesh {
-        let (v, f) = Aabb::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0)).to_trimesh();
-
-        TriMesh::new(v, f).unwrap()
+    fn unit_box() -> TriMesh {
+        box_mesh(Point3::origin(), Point3::new(1.0, 1.0, 1.0))
     }
 
     #[test]
     fn test_collision_scene() {
         let mut scene = CollisionScene::new();
-        let mesh1 = box_mesh();
-        let mesh2 = box_mesh();
+        let mesh1 = unit_box();
+        let mesh2 = unit_box();
 
         let _ = scene.add_background(mesh1);
         let id2 = scene.add_interest(mesh2);
@@ -293,4 +688,320 @@ mod tests {
         let pairs = scene.check_all(&transforms, false, None).unwrap();
         assert_eq!(pairs.len(), 1);
     }
+
+    /// Check every pair the slow way, without the broad phase
+    fn brute_force(scene: &CollisionScene, transforms: &[(usize, Frame3)]) -> Vec<(usize, usize)> {
+        let lookups = scene.quick_lookups(transforms);
+        let mut pairs = Vec::new();
+        for (&id1, mesh1) in scene.meshes.iter() {
+            for (&id2, mesh2) in scene.meshes.iter() {
+                if mesh1.kind == MeshType::Background
+                    || (mesh2.kind == MeshType::Interest && id1 >= id2)
+                    || scene.skip_collision(id1, id2)
+                {
+                    continue;
+                }
+
+                let hit =
+                    shapes_touch(&lookups[&id1], &*mesh1.shape, &lookups[&id2], &*mesh2.shape);
+                if hit.unwrap() {
+                    pairs.push((id1, id2));
+                }
+            }
+        }
+        pairs.sort_unstable();
+        pairs
+    }
+
+    #[test]
+    fn broad_phase_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut scene = CollisionScene::new();
+
+        // A cell full of small fixtures and a handful of moving meshes wandering through it
+        for _ in 0..200 {
+            let mesh = unit_box();
+            let mesh = mesh.scaled(&Vector3::new(
+                rng.random_range(0.2..2.0),
+                rng.random_range(0.2..2.0),
+                rng.random_range(0.2..2.0),
+            ));
+            let id = scene.add_background(mesh);
+            if id.is_multiple_of(7) {
+                scene.remove_mesh(id);
+            }
+        }
+
+        let background = scene.meshes.keys().copied().collect::<Vec<_>>();
+        let mut transforms = background
+            .iter()
+            .map(|&id| {
+                let t = Vector3::new(
+                    rng.random_range(0.0..20.0),
+                    rng.random_range(0.0..20.0),
+                    rng.random_range(0.0..5.0),
+                );
+                (id, Frame3::translation(t.x, t.y, t.z))
+            })
+            .collect::<Vec<_>>();
+
+        let moving = (0..10)
+            .map(|_| scene.add_interest(unit_box().scaled(&Vector3::new(3.0, 1.0, 1.0))))
+            .collect::<Vec<_>>();
+        scene.add_exclusion(moving[0], moving[1]);
+        scene.add_exclusion(moving[2], background[3]);
+
+        for &id in moving.iter() {
+            let t = Vector3::new(
+                rng.random_range(0.0..20.0),
+                rng.random_range(0.0..20.0),
+                rng.random_range(0.0..5.0),
+            );
+            let r = Vector3::new(0.0, 0.0, rng.random_range(0.0..6.0));
+            transforms.push((id, Frame3::new(t, r)));
+        }
+
+        let expected = brute_force(&scene, &transforms);
+        let pairs = scene.check_all(&transforms, false, None).unwrap();
+        assert!(!expected.is_empty());
+        assert_eq!(pairs, expected);
+
+        // Stopping at the first collision keeps the lowest colliding id for each moving mesh
+        let first = scene.check_all(&transforms, true, None).unwrap();
+        let mut lowest = expected.clone();
+        lowest.dedup_by_key(|p| p.0);
+        assert_eq!(first, lowest);
+
+        // Skipped meshes don't appear on either side of a pair
+        let skip = [moving[4], background[10]];
+        let skipped = scene.check_all(&transforms, false, Some(&skip)).unwrap();
+        let remaining = expected
+            .iter()
+            .filter(|(a, b)| !skip.contains(a) && !skip.contains(b))
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(skipped, remaining);
+    }
+
+    #[test]
+    fn primitive_shapes() {
+        use parry3d_f64::shape::{Ball, Capsule, Compound, ConvexPolyhedron, Cuboid, Cylinder};
+
+        let mut scene = CollisionScene::new();
+
+        // A table top with its surface at z = 0, a fence post, and a fixture made of a base plate
+        // with a pin on it
+        let table = scene.add_background(Cuboid::new(Vector3::new(500.0, 500.0, 10.0)));
+        let post = scene.add_background(Cylinder::new(1000.0, 25.0));
+        let fixture = scene.add_background(Compound::new(vec![
+            (Frame3::identity(), SharedShape::cuboid(50.0, 50.0, 5.0)),
+            (Frame3::translation(0.0, 0.0, 30.0), SharedShape::ball(10.0)),
+        ]));
+
+        // Moving shapes of every kind
+        let gripper = scene.add_interest(Capsule::new_z(50.0, 20.0));
+        let part = scene.add_interest(Ball::new(15.0));
+        let hull = ConvexPolyhedron::from_convex_hull(&[
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(40.0, 0.0, 0.0),
+            Point3::new(0.0, 40.0, 0.0),
+            Point3::new(0.0, 0.0, 40.0),
+        ])
+        .unwrap();
+        let wedge = scene.add_interest(hull);
+        let mesh = scene.add_interest(unit_box().scaled(&Vector3::new(30.0, 30.0, 30.0)));
+
+        let mut transforms = vec![
+            (table, Frame3::translation(0.0, 0.0, -10.0)),
+            // The cylinder's axis is y, so stand it up along z
+            (
+                post,
+                Frame3::new(
+                    Vector3::new(800.0, 0.0, 0.0),
+                    Vector3::x() * 90f64.to_radians(),
+                ),
+            ),
+            (fixture, Frame3::translation(-300.0, 0.0, 5.0)),
+            // Resting on the table, through the fixture's pin, clear of everything, and partly
+            // inside the post
+            (gripper, Frame3::translation(0.0, 0.0, 65.0)),
+            (part, Frame3::translation(-300.0, 0.0, 55.0)),
+            (wedge, Frame3::translation(300.0, 300.0, 100.0)),
+            (mesh, Frame3::translation(790.0, 0.0, 200.0)),
+        ];
+
+        let pairs = scene.check_all(&transforms, false, None).unwrap();
+        assert_eq!(pairs, vec![(gripper, table), (part, fixture), (mesh, post)]);
+
+        let d = scene
+            .distances(wedge, &[table, gripper], &transforms)
+            .unwrap();
+        assert!((d[0] - 100.0).abs() < 1e-6);
+        assert!(d[1] > 0.0);
+
+        // A primitive is solid, so a mesh entirely inside one still collides with it
+        let inside = scene.add_interest(unit_box().scaled(&Vector3::new(5.0, 5.0, 5.0)));
+        transforms.push((inside, Frame3::translation(100.0, 100.0, -8.0)));
+        let pairs = scene.check_all(&transforms, false, Some(&[mesh])).unwrap();
+        assert_eq!(
+            pairs,
+            vec![(gripper, table), (part, fixture), (inside, table)]
+        );
+    }
+
+    #[test]
+    fn aligned_primitives_collide() {
+        use parry3d_f64::shape::{Capsule, Cuboid};
+
+        // parry's intersection test alone reports this capsule, centered on the cuboid and
+        // sunk 5 mm into its top, as clear
+        let mut scene = CollisionScene::new();
+        let plate = scene.add_background(Cuboid::new(Vector3::new(500.0, 400.0, 10.0)));
+        let capsule = scene.add_interest(Capsule::new_z(50.0, 20.0));
+        let transforms = vec![(capsule, Frame3::translation(0.0, 0.0, 65.0))];
+
+        let pairs = scene.check_all(&transforms, false, None).unwrap();
+        assert_eq!(pairs, vec![(capsule, plate)]);
+        assert_eq!(
+            scene.distances(capsule, &[plate], &transforms).unwrap(),
+            vec![0.0]
+        );
+    }
+
+    #[test]
+    fn unsupported_pair_is_an_error() {
+        use parry3d_f64::shape::HalfSpace;
+
+        // parry has no intersection test between two half spaces
+        let mut scene = CollisionScene::new();
+        let floor = scene.add_background(HalfSpace::new(Vector3::z_axis()));
+        let wall = scene.add_interest(HalfSpace::new(Vector3::x_axis()));
+
+        let err = scene.check_all(&[], false, None).unwrap_err().to_string();
+        assert!(
+            err.contains(&format!("between {} and {}", wall, floor)),
+            "{}",
+            err
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut scene = CollisionScene::new();
+        let id0 = scene.add_background(unit_box());
+        let id1 = scene.add_interest(unit_box());
+        let id2 = scene.add_interest(unit_box());
+        scene.remove_mesh(id0);
+        scene.add_exclusion(id2, id1);
+
+        let text = serde_json::to_string(&scene).unwrap();
+        let loaded: CollisionScene = serde_json::from_str(&text).unwrap();
+
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded.exclusions, scene.exclusions);
+        assert_eq!(loaded.meshes[&id2].kind, MeshType::Interest);
+        assert_eq!(loaded.next_id, 3);
+
+        // The excluded pair is still skipped after loading
+        let transforms = vec![(id2, Frame3::translation(0.5, 0.5, 0.5))];
+        assert!(
+            loaded
+                .check_all(&transforms, false, None)
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), text);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_rejects_bad_meshes() {
+        let duplicate = r#"{"meshes": [
+            {"id": 0, "kind": "background", "vertices": [[0,0,0],[1,0,0],[0,1,0]], "triangles": [[0,1,2]]},
+            {"id": 0, "kind": "interest", "vertices": [[0,0,0],[1,0,0],[0,1,0]], "triangles": [[0,1,2]]}
+        ]}"#;
+        assert!(serde_json::from_str::<CollisionScene>(duplicate).is_err());
+
+        let bad_kind = r#"{"meshes": [
+            {"id": 0, "kind": "moving", "vertices": [[0,0,0],[1,0,0],[0,1,0]], "triangles": [[0,1,2]]}
+        ]}"#;
+        assert!(serde_json::from_str::<CollisionScene>(bad_kind).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_primitive_shapes() {
+        use parry3d_f64::shape::{Capsule, Compound, ConvexPolyhedron, Cuboid, Cylinder, Segment};
+
+        let mut scene = CollisionScene::new();
+        let table = scene.add_background(Cuboid::new(Vector3::new(500.0, 400.0, 10.0)));
+        let post = scene.add_background(Cylinder::new(1000.0, 25.0));
+        let points = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(40.0, 0.0, 0.0),
+            Point3::new(0.0, 40.0, 0.0),
+            Point3::new(0.0, 0.0, 40.0),
+        ];
+        let wedge = scene.add_background(ConvexPolyhedron::from_convex_hull(&points).unwrap());
+        let fixture = scene.add_background(Compound::new(vec![
+            (Frame3::identity(), SharedShape::cuboid(50.0, 50.0, 5.0)),
+            (Frame3::translation(0.0, 0.0, 30.0), SharedShape::ball(10.0)),
+        ]));
+        let gripper = scene.add_interest(Capsule::new_z(50.0, 20.0));
+        let mesh = scene.add_interest(unit_box());
+
+        let text = serde_json::to_string(&scene).unwrap();
+        assert!(text.contains(r#""shape":"cuboid","half_extents":[500.0,400.0,10.0]"#));
+        let loaded: CollisionScene = serde_json::from_str(&text).unwrap();
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), text);
+
+        // The same layout as `primitive_shapes`, with the small box inside the fixture's pin
+        let transforms = vec![
+            (table, Frame3::translation(0.0, 0.0, -10.0)),
+            (
+                post,
+                Frame3::new(
+                    Vector3::new(800.0, 0.0, 0.0),
+                    Vector3::x() * 90f64.to_radians(),
+                ),
+            ),
+            (wedge, Frame3::translation(300.0, 300.0, 100.0)),
+            (fixture, Frame3::translation(-300.0, 0.0, 5.0)),
+            (gripper, Frame3::translation(0.0, 0.0, 65.0)),
+            (mesh, Frame3::translation(-300.0, 0.0, 40.0)),
+        ];
+        let pairs = loaded.check_all(&transforms, false, None).unwrap();
+        assert_eq!(pairs, vec![(gripper, table), (mesh, fixture)]);
+
+        // Shapes without a saved form are an error rather than being dropped
+        scene.add_background(Segment::new(points[0], points[1]));
+        assert!(serde_json::to_string(&scene).is_err());
+
+        let nested = r#"{"meshes": [
+            {"id": 0, "kind": "background", "shape": "compound", "parts": [
+                {"pose": {"x": 0, "y": 0, "z": 0, "w": 0, "p": 0, "r": 0}, "shape": "compound", "parts": []}
+            ]}
+        ]}"#;
+        assert!(serde_json::from_str::<CollisionScene>(nested).is_err());
+
+        let bad_hull = r#"{"meshes": [
+            {"id": 0, "kind": "background", "shape": "convex_hull", "points": [[0,0,0]]}
+        ]}"#;
+        assert!(serde_json::from_str::<CollisionScene>(bad_hull).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    #[should_panic(expected = "Nested composite shapes are not allowed")]
+    fn serde_compounds_are_never_nested() {
+        use parry3d_f64::shape::Compound;
+
+        // Loading rejects a compound inside a compound. Saving can never produce one because
+        // parry refuses to build it, so every saved compound loads again.
+        Compound::new(vec![(
+            Frame3::identity(),
+            SharedShape::compound(vec![(Frame3::identity(), SharedShape::ball(10.0))]),
+        )]);
+    }
 }
@@ -0,0 +1,327 @@
+//! Allowed-collision matrices, which record for each pair of a robot's links whether the pair
+//! needs to be checked for self-collision at all. Adjacent links touch at their shared joint and
+//! some other pairs touch in every pose the joint limits allow, while many pairs can never reach
+//! each other. Only the remaining pairs need a narrow-phase check, which makes self-collision
+//! checking considerably faster.
+//!
+//! The matrix for a robot is found by sampling random joint angles within its limits and
+//! recording which pairs of link meshes touch. Because it is based on sampling, a pair which only
+//! touches in a tiny region of joint space may be missed and wrongly classified as never
+//! touching, so the number of samples should be large when generating a matrix to ship.
+
+use super::posed_robot::{LINK_COUNT, link_transforms};
+use super::{CollisionScene, shapes_touch};
+use crate::{Result, Robot};
+use parry3d_f64::shape::Shape;
+use rayon::prelude::*;
+
+/// How a pair of links behaved when sampled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkContact {
+    /// The links share a joint, and are never checked against each other
+    Adjacent,
+
+    /// The links touched in every sample
+    Always,
+
+    /// The links never touched in any sample
+    Never,
+
+    /// The links touched in some samples but not in others, so they must be checked
+    Sometimes,
+}
+
+/// A symmetric matrix of how each pair of a robot's seven links (the base followed by the
+/// links moved by J1 through J6) behave with respect to each other
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllowedCollisions {
+    contacts: [[LinkContact; LINK_COUNT]; LINK_COUNT],
+}
+
+impl Default for AllowedCollisions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AllowedCollisions {
+    /// Create a matrix where adjacent links are allowed to collide and every other pair is
+    /// checked, which is the same as the exclusions added by `PosedRobot::new`
+    pub fn new() -> Self {
+        let mut contacts = [[LinkContact::Sometimes; LINK_COUNT]; LINK_COUNT];
+        for i in 0..LINK_COUNT - 1 {
+            contacts[i][i + 1] = LinkContact::Adjacent;
+            contacts[i + 1][i] = LinkContact::Adjacent;
+        }
+        Self { contacts }
+    }
+
+    /// Create a matrix from a list of pairs of link indices and their contact, where every pair
+    /// which isn't listed is adjacent or checked, the same as in `new`
+    pub fn from_pairs(pairs: &[(usize, usize, LinkContact)]) -> Result<Self> {
+        let mut matrix = Self::new();
+        for &(i, j, contact) in pairs {
+            matrix.set(i, j, contact)?;
+        }
+        Ok(matrix)
+    }
+
+    /// Generate a matrix by sampling random joint angles within the robot's limits and checking
+    /// which pairs of link meshes touch. The samples are checked in parallel, and the same seed
+    /// always produces the same matrix. A pair of meshes which parry can't check is an error.
+    ///
+    /// # Arguments
+    ///
+    /// * `robot`: the robot which places the links
+    /// * `meshes`: the seven link meshes, starting with the base, in the layout used by
+    ///   `PosedRobot`
+    /// * `samples`: the number of random sets of joint angles to check
+    /// * `seed`: the seed of the random number generator
+    ///
+    /// returns: Result<AllowedCollisions, Box<dyn Error, Global>>
+    pub fn sample<R: Robot + Sync + ?Sized>(
+        robot: &R,
+        meshes: &[impl Shape],
+        samples: usize,
+        seed: u64,
+    ) -> Result<Self> {
+        if meshes.len() != LINK_COUNT {
+            return Err(format!(
+                "Expected {} link meshes but got {}",
+                LINK_COUNT,
+                meshes.len()
+            )
+            .into());
+        }
+        if samples == 0 {
+            return Err("At least one sample is needed".into());
+        }
+
+        let mut rng = SplitMix64(seed);
+        let limits = robot.joint_limits();
+        let poses = (0..samples)
+            .map(|_| {
+                let mut joints = [0.0; 6];
+                for (j, (lower, upper)) in joints.iter_mut().zip(limits.iter()) {
+                    *j = lower + (upper - lower) * rng.next_f64();
+                }
+                joints
+            })
+            .collect::<Vec<_>>();
+
+        let ids = std::array::from_fn(|i| i);
+        let pairs = (0..LINK_COUNT)
+            .flat_map(|i| (i + 2..LINK_COUNT).map(move |j| (i, j)))
+            .collect::<Vec<_>>();
+
+        // Count how many samples each pair touched in
+        let counts = poses
+            .par_iter()
+            .map(|joints| {
+                let transforms = link_transforms(robot, &ids, joints);
+                pairs
+                    .iter()
+                    .map(|&(i, j)| {
+                        shapes_touch(&transforms[i].1, &meshes[i], &transforms[j].1, &meshes[j])
+                            .map(usize::from)
+                            .map_err(|e| {
+                                format!(
+                                    "Collision check between links {} and {} failed: {}",
+                                    i, j, e
+                                )
+                            })
+                    })
+                    .collect::<std::result::Result<Vec<_>, String>>()
+            })
+            .try_reduce(
+                || vec![0; pairs.len()],
+                |a, b| Ok(a.iter().zip(b).map(|(x, y)| x + y).collect()),
+            )?;
+
+        let mut matrix = Self::new();
+        for (&(i, j), count) in pairs.iter().zip(counts) {
+            let contact = match count {
+                0 => LinkContact::Never,
+                n if n == samples => LinkContact::Always,
+                _ => LinkContact::Sometimes,
+            };
+            matrix.set(i, j, contact)?;
+        }
+
+        Ok(matrix)
+    }
+
+    /// Get the contact of a pair of links by their index, from 0 (the base) to 6
+    pub fn get(&self, i: usize, j: usize) -> Option<LinkContact> {
+        self.contacts.get(i)?.get(j).copied().filter(|_| i != j)
+    }
+
+    /// Set the contact of a pair of links by their index. Adjacent pairs can't be changed.
+    pub fn set(&mut self, i: usize, j: usize, contact: LinkContact) -> Result<()> {
+        if i == j || i >= LINK_COUNT || j >= LINK_COUNT {
+            return Err(format!("Invalid link pair ({}, {})", i, j).into());
+        }
+        if (self.contacts[i][j] == LinkContact::Adjacent) != (contact == LinkContact::Adjacent) {
+            return Err(format!("Links {} and {} are not adjacent", i, j).into());
+        }
+
+        self.contacts[i][j] = contact;
+        self.contacts[j][i] = contact;
+        Ok(())
+    }
+
+    /// Check if a pair of links is allowed to collide, meaning it doesn't need to be checked
+    pub fn is_allowed(&self, i: usize, j: usize) -> bool {
+        self.get(i, j) != Some(LinkContact::Sometimes)
+    }
+
+    /// The pairs of link indices which need to be checked for self-collision, lowest first
+    pub fn checked_pairs(&self) -> Vec<(usize, usize)> {
+        (0..LINK_COUNT)
+            .flat_map(|i| (i + 1..LINK_COUNT).map(move |j| (i, j)))
+            .filter(|&(i, j)| !self.is_allowed(i, j))
+            .collect()
+    }
+
+    /// The pairs of link indices which are not adjacent but don't need to be checked, along
+    /// with their contact, in the form accepted by `from_pairs`
+    pub fn allowed_pairs(&self) -> Vec<(usize, usize, LinkContact)> {
+        (0..LINK_COUNT)
+            .flat_map(|i| (i + 1..LINK_COUNT).map(move |j| (i, j)))
+            .map(|(i, j)| (i, j, self.contacts[i][j]))
+            .filter(|&(_, _, c)| c == LinkContact::Always || c == LinkContact::Never)
+            .collect()
+    }
+
+    /// Add an exclusion to a scene for every pair of links which is allowed to collide
+    ///
+    /// # Arguments
+    ///
+    /// * `scene`: the scene holding the link meshes
+    /// * `links`: the ids of the seven link meshes in the scene, starting with the base
+    ///
+    /// returns: ()
+    pub fn apply(&self, scene: &mut CollisionScene, links: &[usize; LINK_COUNT]) {
+        for i in 0..LINK_COUNT {
+            for j in i + 1..LINK_COUNT {
+                if self.is_allowed(i, j) {
+                    scene.add_exclusion(links[i], links[j]);
+                }
+            }
+        }
+    }
+}
+
+/// A small, fast random number generator, which is all that's needed for spreading samples
+/// over joint space reproducibly
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A uniform value in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fanuc::Crx;
+    use crate::test_helpers::{box_mesh, cube};
+    use crate::{Point3, TriMesh, Vector3};
+    use parry3d_f64::shape::HalfSpace;
+
+    /// Link meshes where the base and the J2 link always overlap at the shared origin, the base
+    /// can never reach the flange, and the upper arm rod can hit the base block
+    fn meshes() -> Vec<TriMesh> {
+        let mut meshes = (0..LINK_COUNT)
+            .map(|_| cube(Point3::new(0.0, 0.0, 1000.0), 1.0))
+            .collect::<Vec<_>>();
+        meshes[0] = box_mesh(
+            Point3::new(-30.0, -30.0, 20.0),
+            Point3::new(30.0, 30.0, 120.0),
+        );
+        meshes[2] = box_mesh(Point3::new(-5.0, -5.0, -10.0), Point3::new(5.0, 5.0, 400.0));
+        meshes[6] = cube(Point3::origin(), 5.0);
+        meshes
+    }
+
+    #[test]
+    fn default_checks_non_adjacent() {
+        let matrix = AllowedCollisions::new();
+        assert_eq!(matrix.get(2, 3), Some(LinkContact::Adjacent));
+        assert_eq!(matrix.get(3, 2), Some(LinkContact::Adjacent));
+        assert_eq!(matrix.get(0, 2), Some(LinkContact::Sometimes));
+        assert_eq!(matrix.get(4, 4), None);
+        assert_eq!(matrix.checked_pairs().len(), 15);
+        assert!(matrix.allowed_pairs().is_empty());
+    }
+
+    #[test]
+    fn set_validates_pairs() -> Result<()> {
+        let mut matrix = AllowedCollisions::new();
+        matrix.set(5, 1, LinkContact::Never)?;
+        assert_eq!(matrix.get(1, 5), Some(LinkContact::Never));
+        assert!(matrix.is_allowed(1, 5));
+
+        assert!(matrix.set(1, 1, LinkContact::Never).is_err());
+        assert!(matrix.set(0, 7, LinkContact::Never).is_err());
+        assert!(matrix.set(1, 2, LinkContact::Never).is_err());
+        assert!(matrix.set(1, 3, LinkContact::Adjacent).is_err());
+
+        let rebuilt = AllowedCollisions::from_pairs(&matrix.allowed_pairs())?;
+        assert_eq!(rebuilt, matrix);
+        Ok(())
+    }
+
+    #[test]
+    fn sampled_matrix() -> Result<()> {
+        let robot = Crx::new_5ia();
+        let matrix = AllowedCollisions::sample(&robot, &meshes(), 300, 7)?;
+
+        assert_eq!(matrix.get(0, 2), Some(LinkContact::Sometimes));
+        assert_eq!(matrix.get(0, 6), Some(LinkContact::Never));
+        assert_eq!(matrix.checked_pairs(), vec![(0, 2)]);
+
+        // The same seed gives the same matrix
+        assert_eq!(
+            AllowedCollisions::sample(&robot, &meshes(), 300, 7)?,
+            matrix
+        );
+        assert!(AllowedCollisions::sample(&robot, &meshes()[..6], 10, 7).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn always_touching() -> Result<()> {
+        // The base and the J2 link both contain the robot origin and cross each other's faces
+        // in every pose
+        let robot = Crx::new_5ia();
+        let mut meshes = meshes();
+        meshes[0] = box_mesh(
+            Point3::new(-30.0, -30.0, -5.0),
+            Point3::new(30.0, 30.0, 5.0),
+        );
+        let matrix = AllowedCollisions::sample(&robot, &meshes, 100, 3)?;
+        assert_eq!(matrix.get(0, 2), Some(LinkContact::Always));
+        Ok(())
+    }
+
+    #[test]
+    fn unsupported_pairs_are_errors() {
+        // parry has no intersection test between two half spaces
+        let meshes = vec![HalfSpace::new(Vector3::z_axis()); LINK_COUNT];
+        let err = AllowedCollisions::sample(&Crx::new_5ia(), &meshes, 10, 7)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Collision check between links"), "{}", err);
+    }
+}
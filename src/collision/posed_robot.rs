@@ -0,0 +1,606 @@
+//! A robot whose link meshes are registered in a `CollisionScene`, so that the scene can be
+//! checked for collisions directly at a set of joint angles.
+
+use super::{AllowedCollisions, CollisionScene, shapes_touch};
+use crate::{Frame3, Result, Robot};
+use parry3d_f64::query::distance;
+use parry3d_f64::shape::Shape;
+
+/// The number of link meshes of a 6-axis arm, the base followed by the links moved by J1
+/// through J6
+pub(crate) const LINK_COUNT: usize = 7;
+
+/// A robot along with the ids of its link meshes in a `CollisionScene`.
+///
+/// The base mesh is placed at the robot's base (the robot origin in the world), and the mesh of
+/// each link moved by J1 through J6 is placed at the matching frame of `fk_all`. This is the
+/// layout of the meshes returned by `fanuc::crx5ia_mesh` and `fanuc::crx10ia_mesh`.
+///
+/// # Examples
+///
+/// ```
+/// use industrial_robots::fanuc::Crx;
+/// use industrial_robots::{CollisionScene, PosedRobot, Point3, TriMesh};
+///
+/// // Stand-in link meshes, a single triangle each
+/// let links = (0..7)
+///     .map(|_| {
+///         let vertices = vec![Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0)];
+///         TriMesh::new(vertices, vec![[0, 1, 2]]).unwrap()
+///     })
+///     .collect();
+///
+/// let mut scene = CollisionScene::new();
+/// let robot = PosedRobot::new(Crx::new_5ia(), &mut scene, links).unwrap();
+/// let collisions = robot.check(&scene, &[0.0, 0.0, 0.0, 0.0, -90.0, 0.0]).unwrap();
+/// ```
+pub struct PosedRobot<R: Robot> {
+    robot: R,
+    links: [usize; LINK_COUNT],
+}
+
+/// The first contact found by a swept collision check
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SweptContact {
+    /// How far along the motion the contact happens, from 0 at the start to 1 at the end
+    pub time: f64,
+
+    /// The joint angles at the time of contact
+    pub joints: [f64; 6],
+
+    /// The ids of the two meshes in contact, with the robot's link first
+    pub pair: (usize, usize),
+}
+
+impl<R: Robot> PosedRobot<R> {
+    /// Register a robot's link meshes in a scene as interest meshes, and exclude each pair of
+    /// adjacent links (which touch at their joint) from being checked against each other.
+    ///
+    /// # Arguments
+    ///
+    /// * `robot`: the robot which places the links
+    /// * `scene`: the scene to add the link meshes to
+    /// * `meshes`: the seven link meshes, starting with the base, each in the frame it will be
+    ///   placed at. These can be any shape, such as a `TriMesh` or a `Capsule`.
+    ///
+    /// returns: Result<PosedRobot<R>, Box<dyn Error, Global>>
+    pub fn new(robot: R, scene: &mut CollisionScene, meshes: Vec<impl Shape>) -> Result<Self> {
+        if meshes.len() != LINK_COUNT {
+            return Err(format!(
+                "Expected {} link meshes but got {}",
+                LINK_COUNT,
+                meshes.len()
+            )
+            .into());
+        }
+
+        let mut links = [0; LINK_COUNT];
+        for (id, mesh) in links.iter_mut().zip(meshes) {
+            *id = scene.add_interest(mesh);
+        }
+
+        for pair in links.windows(2) {
+            scene.add_exclusion(pair[0], pair[1]);
+        }
+
+        Ok(Self { robot, links })
+    }
+
+    pub fn robot(&self) -> &R {
+        &self.robot
+    }
+
+    pub fn robot_mut(&mut self) -> &mut R {
+        &mut self.robot
+    }
+
+    /// The ids of the link meshes in the scene, starting with the base
+    pub fn links(&self) -> &[usize; LINK_COUNT] {
+        &self.links
+    }
+
+    /// The transforms which place each link mesh at a set of joint angles, in the form used by
+    /// `CollisionScene::check_all`
+    pub fn transforms(&self, joints: &[f64; 6]) -> Vec<(usize, Frame3)> {
+        link_transforms(&self.robot, &self.links, joints)
+    }
+
+    /// Check the scene for collisions with the robot at a set of joint angles, returning every
+    /// colliding pair of mesh ids. This covers collisions between the robot and the background,
+    /// and between links which aren't adjacent. Any other interest meshes in the scene are
+    /// checked at their untransformed position.
+    ///
+    /// # Arguments
+    ///
+    /// * `scene`: the scene the link meshes were registered in
+    /// * `joints`: the joint angles in degrees, in the controller convention
+    ///
+    /// returns: Result<Vec<(usize, usize), Global>, Box<dyn Error, Global>>
+    pub fn check(&self, scene: &CollisionScene, joints: &[f64; 6]) -> Result<Vec<(usize, usize)>> {
+        scene.check_all(&self.transforms(joints), false, None)
+    }
+
+    /// Check only for collisions between the robot's own links at a set of joint angles,
+    /// skipping every pair excluded in the scene. With an allowed-collision matrix applied this
+    /// is usually only a handful of narrow-phase checks.
+    ///
+    /// # Arguments
+    ///
+    /// * `scene`: the scene the link meshes were registered in
+    /// * `joints`: the joint angles in degrees, in the controller convention
+    ///
+    /// returns: Result<Vec<(usize, usize), Global>, Box<dyn Error, Global>>
+    pub fn check_self(
+        &self,
+        scene: &CollisionScene,
+        joints: &[f64; 6],
+    ) -> Result<Vec<(usize, usize)>> {
+        let transforms = self.transforms(joints);
+        let mut pairs = Vec::new();
+
+        for (i, (id1, iso1)) in transforms.iter().enumerate() {
+            for (id2, iso2) in transforms[i + 1..].iter() {
+                if scene.skip_collision(*id1, *id2) {
+                    continue;
+                }
+
+                let mesh1 = scene
+                    .meshes
+                    .get(id1)
+                    .ok_or(format!("Mesh id {} not found", id1))?;
+                let mesh2 = scene
+                    .meshes
+                    .get(id2)
+                    .ok_or(format!("Mesh id {} not found", id2))?;
+                let touching =
+                    shapes_touch(iso1, &*mesh1.shape, iso2, &*mesh2.shape).map_err(|e| {
+                        format!("Collision check between {} and {} failed: {}", id1, id2, e)
+                    })?;
+                if touching {
+                    pairs.push((*id1, *id2));
+                }
+            }
+        }
+
+        Ok(pairs)
+    }
+
+    /// Check the whole motion between two sets of joint angles for collisions, rather than only
+    /// the poses at either end. The joints move in a straight line in joint space, the same as a
+    /// joint move on the controller, and the first time at which any of the robot's links comes
+    /// within `tolerance` of something it is checked against is returned.
+    ///
+    /// This uses conservative advancement: the distance from each link to everything it could
+    /// hit is measured, and the motion is advanced by the largest step over which no point of
+    /// the link could have covered that distance. The speed of each link is bounded from the
+    /// change in each joint angle and the distance from each joint axis to the far end of the
+    /// link, so a contact can't be stepped over, and the reported time is never later than the
+    /// actual first contact. Steps shrink as links approach obstacles, so a smaller tolerance
+    /// costs more distance queries.
+    ///
+    /// # Arguments
+    ///
+    /// * `scene`: the scene the link meshes were registered in
+    /// * `start`: the joint angles in degrees at the start of the motion
+    /// * `end`: the joint angles in degrees at the end of the motion
+    /// * `tolerance`: the distance (mm) at which meshes are treated as being in contact, which
+    ///   must be greater than zero
+    ///
+    /// returns: Result<Option<SweptContact>, Box<dyn Error, Global>>
+    pub fn check_swept(
+        &self,
+        scene: &CollisionScene,
+        start: &[f64; 6],
+        end: &[f64; 6],
+        tolerance: f64,
+    ) -> Result<Option<SweptContact>> {
+        if tolerance <= 0.0 {
+            return Err("The contact tolerance must be greater than zero".into());
+        }
+
+        let speeds = self.link_speeds(scene, start, end)?;
+
+        // Every pair of a link and a mesh it is checked against, with the speed at which the
+        // distance between them can shrink. Pairs of links are only listed once.
+        let mut others = scene.meshes.keys().copied().collect::<Vec<_>>();
+        others.sort_unstable();
+        let mut pairs = Vec::new();
+        for (a, &id1) in self.links.iter().enumerate() {
+            for &id2 in others.iter() {
+                if id1 == id2 || scene.skip_collision(id1, id2) {
+                    continue;
+                }
+                match self.links.iter().position(|&l| l == id2) {
+                    Some(b) if b > a => pairs.push((id1, id2, speeds[a] + speeds[b])),
+                    Some(_) => {}
+                    None => pairs.push((id1, id2, speeds[a])),
+                }
+            }
+        }
+
+        let mut time = 0.0;
+        loop {
+            let mut joints = *start;
+            for (j, e) in joints.iter_mut().zip(end.iter()) {
+                *j += (e - *j) * time;
+            }
+
+            let lookups = scene.quick_lookups(&self.transforms(&joints));
+            let mut step = f64::INFINITY;
+            let mut closest: Option<(f64, (usize, usize))> = None;
+
+            for &(id1, id2, speed) in pairs.iter() {
+                let d = distance(
+                    &lookups[&id1],
+                    &*scene.meshes[&id1].shape,
+                    &lookups[&id2],
+                    &*scene.meshes[&id2].shape,
+                )
+                .map_err(|e| format!("Distance check between {} and {} failed: {}", id1, id2, e))?;
+
+                if d <= tolerance && closest.is_none_or(|(c, _)| d < c) {
+                    closest = Some((d, (id1, id2)));
+                }
+                if speed > 0.0 {
+                    step = step.min(d / speed);
+                }
+            }
+
+            if let Some((_, pair)) = closest {
+                return Ok(Some(SweptContact { time, joints, pair }));
+            }
+
+            if time >= 1.0 {
+                return Ok(None);
+            }
+            time = (time + step).min(1.0);
+        }
+    }
+
+    /// An upper bound on how far any point of each link can travel over the motion from `start`
+    /// to `end`. A point on link k moves by the rotation of each joint i before it, at a speed of
+    /// at most the joint's rate times the point's distance from the joint axis. That distance is
+    /// bounded by the lengths between the origins of the joint frames from i to k, which are
+    /// fixed, plus the radius of the link's mesh around its own origin.
+    fn link_speeds(
+        &self,
+        scene: &CollisionScene,
+        start: &[f64; 6],
+        end: &[f64; 6],
+    ) -> Result<[f64; LINK_COUNT]> {
+        let mut delta = [0.0; 6];
+        for (d, (s, e)) in delta.iter_mut().zip(start.iter().zip(end.iter())) {
+            *d = e - s;
+        }
+        let rates = self.robot.kinematic_angles(&delta).map(f64::abs);
+
+        let chain = self.robot.fk_all(start);
+        let lengths = chain
+            .windows(2)
+            .map(|w| (w[1].translation.vector - w[0].translation.vector).norm())
+            .collect::<Vec<_>>();
+
+        let mut speeds = [0.0; LINK_COUNT];
+        for k in 1..LINK_COUNT {
+            let mesh = scene
+                .meshes
+                .get(&self.links[k])
+                .ok_or(format!("Mesh id {} not found", self.links[k]))?;
+            let sphere = mesh.shape.compute_local_bounding_sphere();
+            let radius = sphere.center().coords.norm() + sphere.radius();
+
+            // Joint i (zero based) rotates link k about the origin of chain[i], and link k is
+            // placed at chain[k - 1]
+            speeds[k] = (0..k)
+                .map(|i| rates[i] * (lengths[i..k - 1].iter().sum::<f64>() + radius))
+                .sum();
+        }
+
+        Ok(speeds)
+    }
+
+    /// Exclude every pair of links which an allowed-collision matrix allows to collide, such as
+    /// the matrices shipped with the mesh features
+    pub fn allow_collisions(&self, scene: &mut CollisionScene, allowed: &AllowedCollisions) {
+        allowed.apply(scene, &self.links);
+    }
+
+    /// Check if the robot collides with anything in the scene at a set of joint angles
+    pub fn collides(&self, scene: &CollisionScene, joints: &[f64; 6]) -> Result<bool> {
+        Ok(!scene
+            .check_all(&self.transforms(joints), true, None)?
+            .is_empty())
+    }
+
+    /// Remove the robot's link meshes, and the exclusions involving them, from the scene
+    pub fn remove_from(self, scene: &mut CollisionScene) -> R {
+        for id in self.links {
+            scene.remove_mesh(id);
+        }
+        self.robot
+    }
+}
+
+/// Place the link meshes of a robot at a set of joint angles, with the first id being the base
+/// and the rest following the frames of `fk_all`
+pub(crate) fn link_transforms<R: Robot + ?Sized>(
+    robot: &R,
+    links: &[usize; LINK_COUNT],
+    joints: &[f64; 6],
+) -> Vec<(usize, Frame3)> {
+    let chain = robot.fk_all(joints);
+    let mut transforms = Vec::with_capacity(LINK_COUNT);
+    transforms.push((links[0], robot.base()));
+    transforms.extend(links[1..].iter().copied().zip(chain));
+    transforms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fanuc::Crx;
+    use crate::test_helpers::{box_mesh, cube};
+    use crate::{Point3, TriMesh, Vector3};
+    use parry3d_f64::shape::HalfSpace;
+
+    #[test]
+    fn adjacent_links_excluded() -> Result<()> {
+        let mut scene = CollisionScene::new();
+        let obstacle = scene.add_background(cube(Point3::origin(), 10.0));
+        let meshes = (0..LINK_COUNT)
+            .map(|i| cube(Point3::origin(), 10.0 + i as f64))
+            .collect();
+        let robot = PosedRobot::new(Crx::new_5ia(), &mut scene, meshes)?;
+
+        assert_eq!(scene.len(), 8);
+        assert!(!robot.links().contains(&obstacle));
+        for pair in robot.links().windows(2) {
+            assert!(scene.skip_collision(pair[0], pair[1]));
+        }
+        assert!(!scene.skip_collision(robot.links()[0], robot.links()[2]));
+
+        robot.remove_from(&mut scene);
+        assert_eq!(scene.len(), 1);
+        assert!(scene.exclusions.is_empty());
+        Ok(())
+    }
+
+    /// A robot with tiny link meshes which are all excluded from each other, except for a
+    /// large flange, so that only the flange can hit anything
+    fn flange_only(scene: &mut CollisionScene) -> Result<PosedRobot<Crx>> {
+        let mut meshes = (0..6)
+            .map(|_| cube(Point3::origin(), 1.0))
+            .collect::<Vec<_>>();
+        meshes[0] = box_mesh(
+            Point3::new(-1.0, -1.0, -150.0),
+            Point3::new(1.0, 1.0, -140.0),
+        );
+        meshes.push(cube(Point3::origin(), 20.0));
+        let robot = PosedRobot::new(Crx::new_5ia(), scene, meshes)?;
+        let links = *robot.links();
+        for i in 0..LINK_COUNT {
+            for j in i + 1..LINK_COUNT {
+                scene.add_exclusion(links[i], links[j]);
+            }
+        }
+        Ok(robot)
+    }
+
+    #[test]
+    fn swept_finds_thin_wall() -> Result<()> {
+        let mut scene = CollisionScene::new();
+        let robot = flange_only(&mut scene)?;
+        let start = [0.0, 20.0, -10.0, 0.0, -60.0, 0.0];
+        let end = [60.0, 20.0, -10.0, 0.0, -60.0, 0.0];
+
+        // A small, thin panel facing the direction of travel, which the flange center passes
+        // through halfway along the motion
+        let p = robot
+            .robot()
+            .fk(&[30.0, 20.0, -10.0, 0.0, -60.0, 0.0])
+            .translation
+            .vector;
+        let radial = Vector3::new(p.x, p.y, 0.0).normalize();
+        let corner =
+            |r: f64, z: f64| Point3::from(radial * (p.xy().norm() + r) + Vector3::z() * (p.z + z));
+        let wall = TriMesh::new(
+            vec![
+                corner(-60.0, -60.0),
+                corner(60.0, -60.0),
+                corner(60.0, 60.0),
+                corner(-60.0, 60.0),
+            ],
+            vec![[0, 1, 2], [0, 2, 3]],
+        )
+        .unwrap();
+        let wall = scene.add_background(wall);
+
+        // Neither end touches the wall
+        assert!(robot.check(&scene, &start)?.is_empty());
+        assert!(robot.check(&scene, &end)?.is_empty());
+
+        let contact = robot.check_swept(&scene, &start, &end, 0.1)?.unwrap();
+        assert_eq!(contact.pair, (robot.links()[6], wall));
+
+        // The contact is reported before the flange center reaches the wall, and the flange is
+        // in contact within the tolerance
+        assert!(contact.time > 0.0 && contact.time < 0.5);
+        let d = scene.distances(
+            robot.links()[6],
+            &[wall],
+            &robot.transforms(&contact.joints),
+        )?[0];
+        assert!(d <= 0.1);
+
+        // Finely sampling the motion finds the first contact no earlier than reported
+        let first = (0..=1000)
+            .map(|k| k as f64 / 1000.0)
+            .find(|t| {
+                let mut joints = start;
+                joints[0] = 60.0 * t;
+                !robot.check(&scene, &joints).unwrap().is_empty()
+            })
+            .unwrap();
+        assert!(contact.time <= first && contact.time > first - 0.01);
+
+        // Moving the other way is clear, and starting on the wall is a contact at time zero
+        assert_eq!(
+            robot.check_swept(&scene, &start, &[-60.0, 20.0, -10.0, 0.0, -60.0, 0.0], 0.1)?,
+            None
+        );
+        let on_wall = robot
+            .check_swept(&scene, &contact.joints, &start, 0.1)?
+            .unwrap();
+        assert_eq!(on_wall.time, 0.0);
+        assert!(robot.check_swept(&scene, &start, &end, 0.0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn swept_elbow_motion() -> Result<()> {
+        // J3 is coupled to J2 on the controller, which the speed bound has to account for
+        let mut scene = CollisionScene::new();
+        let robot = flange_only(&mut scene)?;
+        let start = [0.0, 20.0, -10.0, 0.0, -60.0, 0.0];
+        let end = [0.0, 20.0, 50.0, 0.0, -60.0, 0.0];
+
+        let p = robot
+            .robot()
+            .fk(&[0.0, 20.0, 20.0, 0.0, -60.0, 0.0])
+            .translation
+            .vector;
+        let half = Vector3::new(5.0, 5.0, 5.0);
+        let obstacle =
+            scene.add_background(box_mesh(Point3::from(p - half), Point3::from(p + half)));
+        assert!(!robot.collides(&scene, &start)? && !robot.collides(&scene, &end)?);
+
+        let contact = robot.check_swept(&scene, &start, &end, 0.1)?.unwrap();
+        assert_eq!(contact.pair, (robot.links()[6], obstacle));
+
+        let first = (0..=1000)
+            .map(|k| k as f64 / 1000.0)
+            .find(|t| {
+                let mut joints = start;
+                joints[2] = -10.0 + 60.0 * t;
+                robot.collides(&scene, &joints).unwrap()
+            })
+            .unwrap();
+        assert!(contact.time <= first && contact.time > first - 0.01);
+        Ok(())
+    }
+
+    #[test]
+    fn wrong_mesh_count() {
+        let mut scene = CollisionScene::new();
+        let meshes = (0..6).map(|_| cube(Point3::origin(), 1.0)).collect();
+        assert!(PosedRobot::new(Crx::new_5ia(), &mut scene, meshes).is_err());
+        assert!(scene.is_empty());
+    }
+
+    #[test]
+    fn obstacle_collision() -> Result<()> {
+        // Only the flange has a mesh that can reach the obstacle, and the base mesh is kept
+        // clear of the links which share its origin
+        let mut scene = CollisionScene::new();
+        let mut meshes = (0..6)
+            .map(|_| cube(Point3::origin(), 1.0))
+            .collect::<Vec<_>>();
+        meshes[0] = box_mesh(
+            Point3::new(-1.0, -1.0, -150.0),
+            Point3::new(1.0, 1.0, -140.0),
+        );
+        meshes.push(cube(Point3::origin(), 30.0));
+        let robot = PosedRobot::new(Crx::new_5ia(), &mut scene, meshes)?;
+
+        let joints = [20.0, 20.0, -10.0, 0.0, -60.0, 0.0];
+        let flange = robot.robot().fk(&joints).translation.vector;
+        let obstacle = scene.add_background(box_mesh(
+            Point3::from(flange - Vector3::new(10.0, 10.0, 10.0)),
+            Point3::from(flange + Vector3::new(50.0, 50.0, 50.0)),
+        ));
+
+        assert_eq!(
+            robot.check(&scene, &joints)?,
+            vec![(robot.links()[6], obstacle)]
+        );
+        assert!(robot.collides(&scene, &joints)?);
+        assert!(!robot.collides(&scene, &[-20.0, 20.0, -10.0, 0.0, -60.0, 0.0])?);
+        Ok(())
+    }
+
+    #[test]
+    fn self_collision() -> Result<()> {
+        // A block on the base sits in the path of a rod along the upper arm, which is two links
+        // away and so is not excluded
+        let mut scene = CollisionScene::new();
+        let mut meshes = (0..LINK_COUNT)
+            .map(|_| cube(Point3::origin(), 1.0))
+            .collect::<Vec<_>>();
+        meshes[0] = box_mesh(
+            Point3::new(-30.0, -30.0, 80.0),
+            Point3::new(30.0, 30.0, 120.0),
+        );
+        meshes[2] = box_mesh(Point3::new(-5.0, -5.0, 0.0), Point3::new(5.0, 5.0, 400.0));
+        let robot = PosedRobot::new(Crx::new_5ia(), &mut scene, meshes)?;
+
+        let links = robot.links();
+        assert_eq!(
+            robot.check(&scene, &[0.0, 0.0, 0.0, 0.0, -90.0, 0.0])?,
+            vec![(links[0], links[2])]
+        );
+        assert!(!robot.collides(&scene, &[0.0, 45.0, 0.0, 0.0, -90.0, 0.0])?);
+        Ok(())
+    }
+
+    #[test]
+    fn self_collision_only() -> Result<()> {
+        // The same block and rod as above, with an obstacle that only the flange touches
+        let mut scene = CollisionScene::new();
+        let mut meshes = (0..LINK_COUNT)
+            .map(|_| cube(Point3::origin(), 1.0))
+            .collect::<Vec<_>>();
+        meshes[0] = box_mesh(
+            Point3::new(-30.0, -30.0, 80.0),
+            Point3::new(30.0, 30.0, 120.0),
+        );
+        meshes[2] = box_mesh(Point3::new(-5.0, -5.0, 0.0), Point3::new(5.0, 5.0, 400.0));
+        meshes[6] = cube(Point3::origin(), 20.0);
+        let robot = PosedRobot::new(Crx::new_5ia(), &mut scene, meshes)?;
+
+        let joints = [0.0, 0.0, 0.0, 0.0, -90.0, 0.0];
+        let flange = robot.robot().fk(&joints).translation.vector;
+        scene.add_background(box_mesh(
+            Point3::from(flange),
+            Point3::from(flange + Vector3::new(50.0, 50.0, 50.0)),
+        ));
+
+        let links = *robot.links();
+        assert_eq!(robot.check(&scene, &joints)?.len(), 2);
+        assert_eq!(
+            robot.check_self(&scene, &joints)?,
+            vec![(links[0], links[2])]
+        );
+
+        // Once the matrix allows the pair, it is no longer checked
+        let mut allowed = AllowedCollisions::new();
+        allowed.set(0, 2, crate::LinkContact::Always)?;
+        robot.allow_collisions(&mut scene, &allowed);
+        assert!(robot.check_self(&scene, &joints)?.is_empty());
+        assert!(scene.skip_collision(links[0], links[2]));
+        assert!(!scene.skip_collision(links[0], links[3]));
+        Ok(())
+    }
+
+    #[test]
+    fn check_self_reports_unsupported_pairs() -> Result<()> {
+        // parry has no intersection test between two half spaces, so the pair of links can't
+        // be checked and must not be reported as clear
+        let mut scene = CollisionScene::new();
+        let meshes = vec![HalfSpace::new(Vector3::z_axis()); LINK_COUNT];
+        let robot = PosedRobot::new(Crx::new_5ia(), &mut scene, meshes)?;
+
+        let err = robot.check_self(&scene, &[0.0; 6]).unwrap_err().to_string();
+        assert!(err.contains("Collision check between"), "{}", err);
+        Ok(())
+    }
+}
@@ -1,11 +1,25 @@
 //! Module for FANUC robot products.
 
-use crate::{Frame3, Point3, Vector3};
+use crate::nalgebra::Matrix6;
+use crate::{Frame3, Result, Vector3};
+use std::f64::consts::PI;
 
+mod config;
 mod crx;
+mod frame_table;
+mod lr_mate;
+pub mod ls;
+pub mod simulate;
 
-use crate::micro_mesh::bytes_to_mesh;
+#[cfg(any(feature = "mesh_fanuc_crx5ia", feature = "mesh_fanuc_crx10ia"))]
+use crate::{
+    AllowedCollisions, CollisionScene, LinkContact, Point3, PosedRobot, TriMesh,
+    micro_mesh::bytes_to_mesh,
+};
+pub use config::Config;
 pub use crx::Crx;
+pub use frame_table::{FrameTable, UFRAME_COUNT, UTOOL_COUNT};
+pub use lr_mate::LrMate;
 
 #[cfg(feature = "mesh_fanuc_crx5ia")]
 pub fn crx5ia_mesh() -> Vec<(Vec<Point3>, Vec<[u32; 3]>)> {
@@ -33,6 +47,75 @@ pub fn crx10ia_mesh() -> Vec<(Vec<Point3>, Vec<[u32; 3]>)> {
     ]
 }
 
+/// The pairs of CRX links which never touch, found by the `allowed_collisions` example from
+/// 200,000 random samples. The 5iA and 10iA meshes produce the same pairs.
+#[cfg(any(feature = "mesh_fanuc_crx5ia", feature = "mesh_fanuc_crx10ia"))]
+const CRX_NEVER_TOUCHING: [(usize, usize); 7] =
+    [(0, 2), (0, 3), (1, 3), (2, 4), (3, 5), (3, 6), (4, 6)];
+
+#[cfg(any(feature = "mesh_fanuc_crx5ia", feature = "mesh_fanuc_crx10ia"))]
+fn crx_allowed_collisions() -> AllowedCollisions {
+    let pairs = CRX_NEVER_TOUCHING.map(|(i, j)| (i, j, LinkContact::Never));
+    AllowedCollisions::from_pairs(&pairs).unwrap()
+}
+
+/// Register a CRX robot's link meshes in a scene, along with the shipped allowed-collision
+/// matrix, so that only the pairs of links which can actually touch are checked
+#[cfg(any(feature = "mesh_fanuc_crx5ia", feature = "mesh_fanuc_crx10ia"))]
+fn crx_posed(
+    robot: Crx,
+    scene: &mut CollisionScene,
+    meshes: Vec<(Vec<Point3>, Vec<[u32; 3]>)>,
+) -> PosedRobot<Crx> {
+    let meshes = meshes
+        .into_iter()
+        .map(|(v, f)| TriMesh::new(v, f).unwrap())
+        .collect();
+    let posed = PosedRobot::new(robot, scene, meshes).unwrap();
+    posed.allow_collisions(scene, &crx_allowed_collisions());
+    posed
+}
+
+/// The allowed-collision matrix of the CRX-5iA link meshes from `crx5ia_mesh`
+#[cfg(feature = "mesh_fanuc_crx5ia")]
+pub fn crx5ia_allowed_collisions() -> AllowedCollisions {
+    crx_allowed_collisions()
+}
+
+/// The allowed-collision matrix of the CRX-10iA link meshes from `crx10ia_mesh`
+#[cfg(feature = "mesh_fanuc_crx10ia")]
+pub fn crx10ia_allowed_collisions() -> AllowedCollisions {
+    crx_allowed_collisions()
+}
+
+/// Register the CRX-5iA link meshes in a scene as a `PosedRobot`, with the shipped
+/// allowed-collision matrix applied for fast self-collision checks
+///
+/// # Arguments
+///
+/// * `robot`: the robot which places the links, with any base and tool already set
+/// * `scene`: the scene to add the link meshes to
+///
+/// returns: PosedRobot<Crx>
+#[cfg(feature = "mesh_fanuc_crx5ia")]
+pub fn crx5ia_posed(robot: Crx, scene: &mut CollisionScene) -> PosedRobot<Crx> {
+    crx_posed(robot, scene, crx5ia_mesh())
+}
+
+/// Register the CRX-10iA link meshes in a scene as a `PosedRobot`, with the shipped
+/// allowed-collision matrix applied for fast self-collision checks
+///
+/// # Arguments
+///
+/// * `robot`: the robot which places the links, with any base and tool already set
+/// * `scene`: the scene to add the link meshes to
+///
+/// returns: PosedRobot<Crx>
+#[cfg(feature = "mesh_fanuc_crx10ia")]
+pub fn crx10ia_posed(robot: Crx, scene: &mut CollisionScene) -> PosedRobot<Crx> {
+    crx_posed(robot, scene, crx10ia_mesh())
+}
+
 /// This is the transformation which rotates the world XYZ coordinate system to the FANUC flange
 /// convention where Z is pointing directly out of the flange, Y is inverted from the world Y axis,
 /// and X is pointing straight up.
@@ -73,3 +156,181 @@ fn rad_to_joints(rad_joints: &[f64]) -> [f64; 6] {
     joints[2] -= joints[1];
     joints
 }
+
+/// Build the 6x6 geometric Jacobian of a FANUC arm from its kinematic chain. Each column is the
+/// twist of the tool (linear velocity in the top three rows, angular velocity in the bottom
+/// three, both expressed in the robot origin frame) produced by a unit rate of one joint in
+/// radians per second, in the controller convention.
+///
+/// Because the controller J3 angle is measured from the horizontal, moving J2 alone also turns
+/// the kinematic J3 joint by the same amount, so the J2 column is the sum of the kinematic J2 and
+/// J3 columns.
+///
+/// # Arguments
+///
+/// * `chain`: the result of `fk_all`, where joint `i` rotates about `h[i]` at the origin of
+///   `chain[i]`, and its direction is fixed in the frame of `chain[i - 1]`
+/// * `h`: the directions of each joint's axis of rotation
+/// * `tool`: the tool frame in relation to the robot origin, which is the flange frame (the last
+///   frame of the chain) if no tool is set
+///
+/// returns: Matrix<f64, Const<6>, Const<6>, ArrayStorage<f64, 6, 6>>
+fn jacobian(chain: &[Frame3; 6], h: &[Vector3; 6], tool: &Frame3) -> Matrix6<f64> {
+    let tip = tool.translation.vector;
+    let mut jac = Matrix6::zeros();
+    for i in 0..6 {
+        // J1 rotates about its own axis, so its direction is the same in the J1 frame as it is
+        // in the robot base
+        let rotation = if i == 0 {
+            chain[0].rotation
+        } else {
+            chain[i - 1].rotation
+        };
+        let axis = rotation * h[i];
+        let linear = axis.cross(&(tip - chain[i].translation.vector));
+
+        jac.fixed_view_mut::<3, 1>(0, i).copy_from(&linear);
+        jac.fixed_view_mut::<3, 1>(3, i).copy_from(&axis);
+    }
+
+    let j2 = jac.column(1) + jac.column(2);
+    jac.set_column(1, &j2);
+    jac
+}
+
+/// Expand a set of joint angles in degrees into every equivalent set which differs by whole turns
+/// of one or more joints and lies within the joint limits. Each FANUC joint (including J3 in the
+/// controller convention) has a period of 360 degrees, so every combination of turns reaches the
+/// same flange pose. If a joint has no value within its limits the result is empty.
+///
+/// J2 and J3 are expanded along with the wrapped J1, J4, and J6. The controller's J3 is the
+/// difference between two wrapped kinematic angles (`rad_to_joints` subtracts J2), so it can come
+/// out a whole turn away from the value the controller uses, e.g. -282.9 instead of 77.1 when J2
+/// is 137.1. Expanding J3 as well and filtering by the limits recovers that value. J2 is treated
+/// the same way so that no joint is special cased, but its limits on the current models are
+/// within a half turn, so it never gains a variant.
+///
+/// # Arguments
+///
+/// * `joints`: the joint angles in degrees, in the controller convention
+/// * `limits`: the lower and upper limits of each joint in degrees
+///
+/// returns: Vec<[f64; 6], Global>
+fn turn_variants(joints: &[f64; 6], limits: &[(f64, f64); 6]) -> Vec<[f64; 6]> {
+    let mut results = vec![*joints];
+    for (i, (lower, upper)) in limits.iter().enumerate() {
+        let first = ((lower - joints[i]) / 360.0).ceil() as i32;
+        let last = ((upper - joints[i]) / 360.0).floor() as i32;
+
+        results = results
+            .iter()
+            .flat_map(|r| {
+                (first..=last).map(move |k| {
+                    let mut v = *r;
+                    v[i] += 360.0 * k as f64;
+                    v
+                })
+            })
+            .collect();
+    }
+
+    results
+}
+
+/// Check that every joint's limits are finite and that the lower limit is not above the upper
+fn check_limits(limits: &[(f64, f64); 6]) -> Result<()> {
+    for (i, (lower, upper)) in limits.iter().enumerate() {
+        if !lower.is_finite() || !upper.is_finite() || lower > upper {
+            return Err(format!(
+                "Invalid limits for J{}: lower limit must be finite and not above the upper",
+                i + 1
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Wrap an angle in radians to the range (-pi, pi]
+fn wrap(angle: f64) -> f64 {
+    let wrapped = angle.rem_euclid(2.0 * PI);
+    if wrapped > PI {
+        wrapped - 2.0 * PI
+    } else {
+        wrapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turn_variants_within_limits() {
+        let limits = [
+            (-180.0, 180.0),
+            (-180.0, 180.0),
+            (-270.0, 270.0),
+            (-190.0, 190.0),
+            (-180.0, 180.0),
+            (-450.0, 450.0),
+        ];
+        let variants = turn_variants(&[10.0, 0.0, 100.0, 175.0, 0.0, 0.0], &limits);
+
+        // J3 has two values, J4 has two values, and J6 has three
+        assert_eq!(variants.len(), 12);
+        assert!(variants.contains(&[10.0, 0.0, -260.0, -185.0, 0.0, -360.0]));
+        assert!(variants.contains(&[10.0, 0.0, 100.0, 175.0, 0.0, 360.0]));
+    }
+
+    /// Check a shipped CRX matrix: the home pose is free of self-collision, and a fresh, smaller
+    /// sample of the link meshes never finds contact in a pair the matrix skips
+    #[cfg(any(feature = "mesh_fanuc_crx5ia", feature = "mesh_fanuc_crx10ia"))]
+    fn assert_shipped_matrix(
+        robot: Crx,
+        meshes: Vec<(Vec<Point3>, Vec<[u32; 3]>)>,
+        matrix: AllowedCollisions,
+    ) {
+        let mut scene = CollisionScene::new();
+        let posed = crx_posed(robot.clone(), &mut scene, meshes.clone());
+        assert_eq!(scene.len(), 7);
+        assert!(
+            posed
+                .check_self(&scene, &[0.0, 0.0, 0.0, 0.0, -90.0, 0.0])
+                .unwrap()
+                .is_empty()
+        );
+
+        let meshes = meshes
+            .into_iter()
+            .map(|(v, f)| TriMesh::new(v, f).unwrap())
+            .collect::<Vec<_>>();
+        let sampled = AllowedCollisions::sample(&robot, &meshes, 100, 99).unwrap();
+        for (i, j) in CRX_NEVER_TOUCHING {
+            assert_eq!(sampled.get(i, j), Some(LinkContact::Never));
+        }
+        assert_eq!(matrix.checked_pairs().len(), 8);
+    }
+
+    #[cfg(feature = "mesh_fanuc_crx5ia")]
+    #[test]
+    fn crx5ia_shipped_matrix() {
+        assert_shipped_matrix(Crx::new_5ia(), crx5ia_mesh(), crx5ia_allowed_collisions());
+    }
+
+    #[cfg(feature = "mesh_fanuc_crx10ia")]
+    #[test]
+    fn crx10ia_shipped_matrix() {
+        assert_shipped_matrix(
+            Crx::new_10ia(),
+            crx10ia_mesh(),
+            crx10ia_allowed_collisions(),
+        );
+    }
+
+    #[test]
+    fn turn_variants_outside_limits() {
+        let limits = [(-10.0, 10.0); 6];
+        assert!(turn_variants(&[20.0, 0.0, 0.0, 0.0, 0.0, 0.0], &limits).is_empty());
+    }
+}
@@ -0,0 +1,238 @@
+//! The FANUC configuration string, which distinguishes between the different joint solutions
+//! that reach the same Cartesian position. On the controller this is displayed and stored
+//! alongside every Cartesian position, for example `N U T, 0, 0, 0`.
+
+use crate::Frame3;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// A FANUC arm configuration, made up of the flip, up, and front flags along with the turn
+/// numbers of J1, J4, and J6. With the `serde` feature it is serialized as its configuration
+/// string, such as `"N U T, 0, 0, 0"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "String", try_from = "String")
+)]
+pub struct Config {
+    /// `F` (true) when J5 is positive, `N` (false) otherwise
+    pub flip: bool,
+
+    /// `U` (true) when the elbow is above the line from the J2 axis to the wrist, `D` (false)
+    /// when it is below
+    pub up: bool,
+
+    /// `T` (true) when the wrist is in front of the J1 axis, `B` (false) when it is behind
+    pub front: bool,
+
+    /// The turn numbers of J1, J4, and J6. A turn number of zero covers (-180, 180] degrees,
+    /// one covers (180, 540], negative one covers (-540, -180], and so on.
+    pub turns: [i32; 3],
+}
+
+impl Config {
+    pub fn new(flip: bool, up: bool, front: bool, turns: [i32; 3]) -> Self {
+        Self {
+            flip,
+            up,
+            front,
+            turns,
+        }
+    }
+
+    /// Determine the configuration of a FANUC arm from its joint angles and the kinematic chain
+    /// produced by `fk_all` for those joint angles. The chain must follow the layout used by the
+    /// FANUC models in this crate, where the second, third, and fifth frames are located on the
+    /// J2, J3, and J5 axes respectively.
+    ///
+    /// # Arguments
+    ///
+    /// * `joints`: the joint angles in degrees, in the controller convention
+    /// * `chain`: the result of `fk_all` for the same joint angles
+    ///
+    /// returns: Config
+    pub(crate) fn from_chain(joints: &[f64; 6], chain: &[Frame3; 6]) -> Self {
+        // Everything is measured in the frame of J1, where the plane of the arm is XZ
+        let to_arm = chain[0].inverse();
+        let shoulder = to_arm * chain[1].translation.vector;
+        let elbow = to_arm * chain[2].translation.vector;
+        let wrist = to_arm * chain[4].translation.vector;
+
+        let front = wrist.x >= 0.0;
+
+        // The side of the shoulder-wrist line which the elbow is on, flipped when the arm
+        // reaches backwards so that "up" always means towards the sky
+        let d = wrist - shoulder;
+        let e = elbow - shoulder;
+        let side = d.x * e.z - d.z * e.x;
+        let up = if front { side >= 0.0 } else { side <= 0.0 };
+
+        Self {
+            flip: joints[4] > 0.0,
+            up,
+            front,
+            turns: [turn(joints[0]), turn(joints[3]), turn(joints[5])],
+        }
+    }
+
+    /// Check if the flip, up, and front flags of two configurations match, ignoring the turn
+    /// numbers.
+    pub fn same_arm(&self, other: &Config) -> bool {
+        self.flip == other.flip && self.up == other.up && self.front == other.front
+    }
+
+    /// Shift J1, J4, and J6 of a set of joint angles by whole turns so that they match the turn
+    /// numbers of this configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `joints`: the joint angles in degrees, in the controller convention
+    ///
+    /// returns: [f64; 6]
+    pub fn apply_turns(&self, joints: &[f64; 6]) -> [f64; 6] {
+        let mut result = *joints;
+        for (i, t) in [0, 3, 5].iter().zip(self.turns.iter()) {
+            result[*i] += 360.0 * (t - turn(joints[*i])) as f64;
+        }
+        result
+    }
+}
+
+/// The FANUC turn number of a joint angle in degrees
+fn turn(angle: f64) -> i32 {
+    ((angle - 180.0) / 360.0).ceil() as i32
+}
+
+impl Display for Config {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}, {}, {}, {}",
+            if self.flip { 'F' } else { 'N' },
+            if self.up { 'U' } else { 'D' },
+            if self.front { 'T' } else { 'B' },
+            self.turns[0],
+            self.turns[1],
+            self.turns[2]
+        )
+    }
+}
+
+impl FromStr for Config {
+    type Err = Box<dyn std::error::Error>;
+
+    /// Parse a configuration string as it appears on the controller or in an LS file, such as
+    /// `N U T, 0, 0, 0` or `FDB, 1, 0, -1`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.split(',').map(|p| p.trim()).collect::<Vec<_>>();
+        if parts.len() != 4 {
+            return Err(format!("Invalid FANUC configuration '{}'", s).into());
+        }
+
+        let flags = parts[0]
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| c.to_ascii_uppercase())
+            .collect::<Vec<_>>();
+
+        let (flip, up, front) = match flags.as_slice() {
+            [f, u, t] => (
+                flag(*f, 'F', 'N', s)?,
+                flag(*u, 'U', 'D', s)?,
+                flag(*t, 'T', 'B', s)?,
+            ),
+            _ => return Err(format!("Invalid FANUC configuration '{}'", s).into()),
+        };
+
+        let mut turns = [0; 3];
+        for (t, p) in turns.iter_mut().zip(parts[1..].iter()) {
+            *t = p
+                .parse()
+                .map_err(|_| format!("Invalid turn number '{}' in '{}'", p, s))?;
+        }
+
+        Ok(Self::new(flip, up, front, turns))
+    }
+}
+
+impl From<Config> for String {
+    fn from(config: Config) -> Self {
+        config.to_string()
+    }
+}
+
+impl TryFrom<String> for Config {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+fn flag(c: char, yes: char, no: char, s: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    if c == yes {
+        Ok(true)
+    } else if c == no {
+        Ok(false)
+    } else {
+        Err(format!("Expected '{}' or '{}' in FANUC configuration '{}'", yes, no, s).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(0.0, 0)]
+    #[test_case(180.0, 0)]
+    #[test_case(180.1, 1)]
+    #[test_case(540.0, 1)]
+    #[test_case(-180.0, -1)]
+    #[test_case(-179.9, 0)]
+    #[test_case(-540.1, -2)]
+    fn turn_numbers(angle: f64, expected: i32) {
+        assert_eq!(turn(angle), expected);
+    }
+
+    #[test_case("N U T, 0, 0, 0", Config::new(false, true, true, [0, 0, 0]))]
+    #[test_case("F D B, 1, 0, -1", Config::new(true, false, false, [1, 0, -1]))]
+    #[test_case("fut,0,-2,0", Config::new(true, true, true, [0, -2, 0]))]
+    fn parse_config(s: &str, expected: Config) {
+        assert_eq!(s.parse::<Config>().unwrap(), expected);
+    }
+
+    #[test_case("N U T")]
+    #[test_case("N X T, 0, 0, 0")]
+    #[test_case("N U, 0, 0, 0")]
+    #[test_case("N U T, 0, a, 0")]
+    fn parse_invalid(s: &str) {
+        assert!(s.parse::<Config>().is_err());
+    }
+
+    #[test]
+    fn display_round_trip() {
+        let config = Config::new(true, false, true, [-1, 0, 2]);
+        let text = config.to_string();
+        assert_eq!(text, "F D T, -1, 0, 2");
+        assert_eq!(text.parse::<Config>().unwrap(), config);
+    }
+
+    #[test]
+    fn apply_turns() {
+        let config = Config::new(false, true, true, [1, -1, 0]);
+        let joints = config.apply_turns(&[10.0, 20.0, 30.0, 40.0, 50.0, 200.0]);
+        assert_eq!(joints, [370.0, 20.0, 30.0, -320.0, 50.0, -160.0]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_as_string() {
+        let config = Config::new(true, false, true, [-1, 0, 2]);
+        let text = serde_json::to_string(&config).unwrap();
+        assert_eq!(text, "\"F D T, -1, 0, 2\"");
+        assert_eq!(serde_json::from_str::<Config>(&text).unwrap(), config);
+        assert!(serde_json::from_str::<Config>("\"N X T, 0, 0, 0\"").is_err());
+    }
+}
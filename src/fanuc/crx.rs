@@ -7,11 +7,61 @@
 //! forearm parallel to the robot base.  This means that to use any kinematics model for robots in
 //! this series, the J2/J3 angles must be modified on their way in and out.
 
-use crate::fanuc::{end_adjust, joints_to_rad, rad_to_joints};
-use crate::nalgebra::{Translation, UnitQuaternion};
+use crate::fanuc::{
+    Config, check_limits, end_adjust, jacobian, joints_to_rad, rad_to_joints, turn_variants, wrap,
+};
+use crate::helpers::{brent, golden_min};
+use crate::nalgebra::{Matrix6, Translation, UnitQuaternion};
 use crate::type_aliases::Frame3;
-use crate::{Point3, Vector3};
-
+use crate::{Point3, Result, Robot, Vector3};
+use std::f64::consts::PI;
+
+#[cfg(feature = "serde")]
+use crate::XyzWpr;
+
+/// Joint limits in degrees (controller convention) shared by the CRX series datasheets
+const CRX_LIMITS: [(f64, f64); 6] = [
+    (-180.0, 180.0),
+    (-180.0, 180.0),
+    (-270.0, 270.0),
+    (-190.0, 190.0),
+    (-180.0, 180.0),
+    (-225.0, 225.0),
+];
+
+/// The number of regularly spaced samples around the O4 candidate circle used to bracket the
+/// IK roots
+const IK_SAMPLES: usize = 360;
+
+/// The largest error (in mm) at which a polished root is accepted as a real solution. Polished
+/// crossings land far below this, so it only has to absorb round-off at tangent roots and reject
+/// the discontinuity which occurs when a candidate passes over the robot origin.
+const IK_ROOT_TOL: f64 = 1e-7;
+
+/// The distance (in mm) from the J1 axis at which an O4 candidate is treated as lying on it
+const IK_AXIS_TOL: f64 = 1e-6;
+
+/// The error (in mm) below which every plane angle at an axis crossing is treated as a solution.
+/// Along such a family the error is zero in theory, but the rounding in the target frame is
+/// amplified by the elbow geometry and it is only small rather than exactly zero.
+const IK_FAMILY_TOL: f64 = 1e-5;
+
+/// The distance (in mm) from the J1 axis inside of which regular roots are discarded when the
+/// O4 candidate circle crosses the axis
+const IK_AXIS_NEAR: f64 = 1e-3;
+
+/// A robot from the FANUC CRX series of collaborative arms.
+///
+/// With the `serde` feature the robot is serialized by its link dimensions in mm, its joint
+/// limits in degrees, and its tool and base frames as XYZ and WPR values. The joint limits,
+/// tool, and base are optional when deserializing, and default to the datasheet limits and
+/// identity frames.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "CrxDef", try_from = "CrxDef")
+)]
 pub struct Crx {
     z0: f64,
     z1: f64,
@@ -20,6 +70,9 @@ pub struct Crx {
     y1: f64,
     h: [Vector3; 6],
     ik_d: f64,
+    limits: [(f64, f64); 6],
+    tool: Frame3,
+    base: Frame3,
 }
 
 impl Crx {
@@ -50,6 +103,61 @@ impl Crx {
         self.y1
     }
 
+    /// The lower and upper limits of each joint in degrees, as they would be displayed in the
+    /// FANUC controller.
+    pub fn joint_limits(&self) -> [(f64, f64); 6] {
+        self.limits
+    }
+
+    /// The tool frame (the FANUC UTOOL) relative to the flange. Forward kinematics return the
+    /// pose of this frame, and inverse kinematics targets are poses for it. With no tool set this
+    /// is the identity, and both work directly with the flange.
+    pub fn tool(&self) -> Frame3 {
+        self.tool
+    }
+
+    /// Set the tool frame (the FANUC UTOOL) relative to the flange, such as the TCP of a gripper
+    pub fn set_tool(&mut self, tool: Frame3) {
+        self.tool = tool;
+    }
+
+    /// Return the robot with a tool frame (the FANUC UTOOL) attached relative to the flange
+    pub fn with_tool(mut self, tool: Frame3) -> Self {
+        self.tool = tool;
+        self
+    }
+
+    /// The pose of the robot origin in the world. Forward kinematics return poses in the world,
+    /// and inverse kinematics targets are poses in the world. With no base set this is the
+    /// identity, and the world is the robot origin, the same as on the controller.
+    pub fn base(&self) -> Frame3 {
+        self.base
+    }
+
+    /// Set the pose of the robot origin in the world
+    pub fn set_base(&mut self, base: Frame3) {
+        self.base = base;
+    }
+
+    /// Return the robot with its origin placed at a pose in the world
+    pub fn with_base(mut self, base: Frame3) -> Self {
+        self.base = base;
+        self
+    }
+
+    /// Place the robot by the bottom of its mounting flange, such as on the top surface of a
+    /// table, a rail carriage, or an angled bracket. The robot origin sits `z0` above the
+    /// mounting flange along its Z axis.
+    pub fn set_mount(&mut self, mount: Frame3) {
+        self.base = mount * Frame3::translation(0.0, 0.0, self.z0);
+    }
+
+    /// Return the robot placed by the bottom of its mounting flange. See `set_mount`.
+    pub fn with_mount(mut self, mount: Frame3) -> Self {
+        self.set_mount(mount);
+        self
+    }
+
     /// Internal constructor for the CRX series of robots.
     ///
     /// # Arguments
@@ -83,7 +191,71 @@ impl Crx {
             y1,
             h,
             ik_d,
+            limits: CRX_LIMITS,
+            tool: Frame3::identity(),
+            base: Frame3::identity(),
+        }
+    }
+
+    /// Creates a CRX-like robot from user supplied link dimensions, such as those identified
+    /// by calibrating a real robot whose links differ slightly from the datasheet. The
+    /// dimensions are validated so that the inverse kinematics remain well-defined, and an error
+    /// is returned for any value which is not finite or which would collapse part of the arm.
+    ///
+    /// # Arguments
+    ///
+    /// * `z0`: The height from the bottom of the mounting flange to the world origin. This doesn't
+    ///   affect the kinematics, but is a necessary value for doing layout and position studies.
+    /// * `z1`: The height from the J2 axis to the J3 axis, must be positive.
+    /// * `x1`: The length from the J3 axis to the J5 axis, must be positive.
+    /// * `x2`: The length from the J5 axis to the robot flange, must not be negative.
+    /// * `y1`: The offset from the J1 axis to the J2 axis, must be positive.
+    ///
+    /// returns: Result<Crx, Box<dyn Error, Global>>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use industrial_robots::fanuc::Crx;
+    ///
+    /// let robot = Crx::from_dimensions(185.0, 410.2, 429.8, 145.0, 130.1).unwrap();
+    /// assert!(Crx::from_dimensions(185.0, 410.0, 430.0, 145.0, 0.0).is_err());
+    /// ```
+    pub fn from_dimensions(z0: f64, z1: f64, x1: f64, x2: f64, y1: f64) -> Result<Self> {
+        let values = [("z0", z0), ("z1", z1), ("x1", x1), ("x2", x2), ("y1", y1)];
+        if let Some((name, _)) = values.iter().find(|(_, v)| !v.is_finite()) {
+            return Err(format!("CRX dimension {} must be a finite value", name).into());
+        }
+
+        if z0 < 0.0 || x2 < 0.0 {
+            return Err("CRX dimensions z0 and x2 must not be negative".into());
         }
+
+        // The upper arm and forearm are the two radii used to find the elbow position, so if
+        // either is zero the elbow is undefined. Without the y1 offset every candidate point
+        // for the J5 axis collapses onto the same point and no solution can be bracketed.
+        if z1 <= 0.0 || x1 <= 0.0 || y1 <= 0.0 {
+            return Err("CRX dimensions z1, x1, and y1 must be positive".into());
+        }
+
+        Ok(Self::new(z0, z1, x1, x2, y1))
+    }
+
+    /// Replace the joint limits of the robot, for example to match the software limits set on a
+    /// particular controller. Solutions from `ik` are expanded and filtered against these
+    /// limits, so a joint whose range covers more than a single turn produces every equivalent
+    /// angle that fits.
+    ///
+    /// # Arguments
+    ///
+    /// * `limits`: the lower and upper limit of each joint in degrees, in the controller
+    ///   convention
+    ///
+    /// returns: Result<Crx, Box<dyn Error, Global>>
+    pub fn with_joint_limits(mut self, limits: [(f64, f64); 6]) -> Result<Self> {
+        check_limits(&limits)?;
+        self.limits = limits;
+        Ok(self)
     }
 
     /// Creates a new CRX-5iA robot
@@ -91,16 +263,44 @@ impl Crx {
         Self::new(185.0, 410.0, 430.0, 145.0, 130.0)
     }
 
+    /// Creates a new CRX-10iA robot
     pub fn new_10ia() -> Self {
         Self::new(245.0, 540.0, 540.0, 160.0, 150.0)
     }
 
+    /// Creates a new CRX-10iA/L robot, using the nominal link lengths from the arm dimension
+    /// drawing in FANUC's CRX-10iA/L datasheet. These have not been checked against a controller.
+    pub fn new_10ia_l() -> Self {
+        Self::new(245.0, 710.0, 540.0, 160.0, 150.0)
+    }
+
+    /// Creates a new CRX-20iA/L robot, which shares its arm dimensions with the CRX-10iA/L
+    /// according to FANUC's CRX-20iA/L datasheet. These have not been checked against a
+    /// controller.
+    pub fn new_20ia_l() -> Self {
+        Self::new(245.0, 710.0, 540.0, 160.0, 150.0)
+    }
+
+    /// Creates a new CRX-25iA robot, using the nominal link lengths from the arm dimension
+    /// drawing in FANUC's CRX-25iA datasheet. These have not been checked against a controller.
+    pub fn new_25ia() -> Self {
+        Self::new(370.0, 950.0, 750.0, 185.0, 220.0)
+    }
+
+    /// Creates a new CRX-30iA robot, using the nominal link lengths from the arm dimension
+    /// drawing in FANUC's CRX-30iA datasheet. These have not been checked against a controller.
+    pub fn new_30ia() -> Self {
+        Self::new(370.0, 880.0, 690.0, 185.0, 220.0)
+    }
+
     /// Compute the forward kinematics of a series of joint angles for the CRX series of robots.
     /// The joints should be provided in degrees as they would appear in the robot controller. The
     /// output will be a `Frame3` object representing the position and orientation of the robot's
-    /// flange in relation to the robot origin.
+    /// tool frame (or the flange, if no tool is set) in relation to the world, which is the robot
+    /// origin unless a base is set.
     ///
-    /// The output frame will match the FANUC controller in position and orientation.
+    /// With no base set, the output frame will match the FANUC controller in position and
+    /// orientation.
     ///
     /// # Arguments
     ///
@@ -109,32 +309,33 @@ impl Crx {
     ///
     /// returns: Isometry<f64, Unit<Quaternion<f64>>, 3>
     pub fn fk(&self, joints: &[f64; 6]) -> Frame3 {
-        self.fk_all(joints)[5]
+        self.fk_all(joints)[5] * self.tool
     }
 
     /// Compute the forward kinematics of a series of joint angles for the CRX series of robots,
     /// returning the full kinematic chain for each joint in the robot. This will return an array
     /// of `Frame3` objects representing the position and orientation of each joint in relation
-    /// to the robot origin. This can be useful for visualizing the full kinematic chain of the
+    /// to the world. This can be useful for visualizing the full kinematic chain of the
     /// robot and understanding how each joint contributes to the overall position and
     /// orientation of the robot's flange.
     ///
     /// The final frame in the array will represent the position and orientation of the robot's
-    /// flange, and will be identical to the result of the `forward` method, matching the expected
-    /// value of the actual robot controller. The other frames in the array will be at the
-    /// kinematic link origins, and do not have any corresponding values in the actual robot.
+    /// flange, and will be identical to the result of the `fk` method when no tool is set,
+    /// matching the expected value of the actual robot controller. The other frames in the array
+    /// will be at the kinematic link origins, and do not have any corresponding values in the
+    /// actual robot.
     ///
     /// # Arguments
     ///
     /// * `joints`: The joint angles for the robot in degrees. This should be an array of 6 values
-    ///  representing the angles for each joint in the order of J1, J2, J3, J4, J5, and J6.
+    ///   representing the angles for each joint in the order of J1, J2, J3, J4, J5, and J6.
     ///
     /// returns: [Isometry<f64, Unit<Quaternion<f64>>, 3>; 6]
     pub fn fk_all(&self, joints: &[f64; 6]) -> [Frame3; 6] {
         let joints = joints_to_rad(joints);
 
-        // The first link is at the origin, rotated by the first joint angle
-        let f1 = Frame3::rotation(self.h[0] * joints[0]);
+        // The first link is at the robot origin, rotated by the first joint angle
+        let f1 = self.base * Frame3::rotation(self.h[0] * joints[0]);
 
         // J1->J2 has no origin shift
         let f2 = f1 * Frame3::rotation(self.h[1] * joints[1]);
@@ -168,47 +369,144 @@ impl Crx {
         [f1, f2, f3, f4, f5, f6]
     }
 
-    ///
+    /// Compute the 6x6 geometric Jacobian of the robot for a set of joint angles in degrees. The
+    /// Jacobian maps joint rates in radians per second, in the controller convention, to the
+    /// twist of the tool frame expressed in the world frame. The top three rows are the
+    /// linear velocity of the tool origin (mm/s) and the bottom three are its angular velocity
+    /// (rad/s).
     ///
     /// # Arguments
     ///
-    /// * `d`:
+    /// * `joints`: The joint angles for the robot in degrees, as they would appear in the
+    ///   controller.
     ///
-    /// returns: Option<(f64, f64)>
+    /// returns: Matrix<f64, Const<6>, Const<6>, ArrayStorage<f64, 6, 6>>
+    pub fn jacobian(&self, joints: &[f64; 6]) -> Matrix6<f64> {
+        let chain = self.fk_all(joints);
+        jacobian(&chain, &self.h, &(chain[5] * self.tool))
+    }
+
+    /// Compute the inverse kinematics for a target tool frame, returning every set of joint angles
+    /// (in degrees, as they would appear in the controller) which places the tool at the target.
     ///
-    /// # Examples
+    /// The CRX has no closed-form solution because of its non-spherical wrist. Instead, the
+    /// position of the J5 axis is found from the target, and the candidate points for the end of
+    /// the forearm lie on a circle around it, parameterized by an angle theta. A solution exists
+    /// wherever the distance error for one of the two elbow candidates is zero. Every root of
+    /// the error function, including tangent (double) roots, is bracketed and then polished to
+    /// machine precision, and each root produces one solution for J1 pointing towards the
+    /// forearm and one pointing away from it.
     ///
-    /// ```
+    /// Every joint which has a range larger than a single turn is expanded into each of its
+    /// equivalent values (360 degrees apart) that fit within the joint limits, and solutions
+    /// which can't be brought within the limits are discarded.
     ///
-    /// ```
+    /// # Arguments
+    ///
+    /// * `target`: the desired position and orientation of the tool frame (the flange if no tool
+    ///   is set) in relation to the world
+    ///
+    /// returns: Vec<[f64; 6], Global>
     pub fn ik(&self, target: &Frame3) -> Vec<[f64; 6]> {
+        let target = &(self.base.inverse() * target * self.tool.inverse());
         let mut results = Vec::new();
-        let (upper_results, lower_results) = self.brute_force_o3(target);
-
-        for theta in upper_results.iter() {
-            let (o4, o3s) = self.get_candidates(*theta, target);
-            if let Some((u, _)) = o3s {
-                results.push(self.calculate_joint_radians(&u, &o4, target));
-            }
+        let (upper_results, lower_results) = self.theta_roots(target);
+        let crossings = self.axis_crossings(target);
+
+        // Roots found right beside an axis crossing are the regular branches running into the
+        // singular point, and are replaced by the exact solutions from `axis_candidates`
+        let regular =
+            |theta: &f64| crossings.is_empty() || self.o4_radius(*theta, target) > IK_AXIS_NEAR;
+
+        let mut candidates = upper_results
+            .iter()
+            .filter(|t| regular(t))
+            .filter_map(|theta| {
+                let (o4, o3s) = self.get_candidates(*theta, target);
+                o3s.map(|(u, _)| (u, o4, o4.y.atan2(o4.x)))
+            })
+            .chain(
+                lower_results
+                    .iter()
+                    .filter(|t| regular(t))
+                    .filter_map(|theta| {
+                        let (o4, o3s) = self.get_candidates(*theta, target);
+                        o3s.map(|(_, l)| (l, o4, o4.y.atan2(o4.x)))
+                    }),
+            )
+            .collect::<Vec<_>>();
+
+        // Where the O4 candidate lies on the J1 axis the plane of the arm can't be found from
+        // O4, so the plane angle is solved for directly instead
+        for (theta, _) in crossings.iter() {
+            candidates.extend(self.axis_candidates(*theta, target));
         }
 
-        for theta in lower_results.iter() {
-            let (o4, o3s) = self.get_candidates(*theta, target);
-            if let Some((_, l)) = o3s {
-                results.push(self.calculate_joint_radians(&l, &o4, target));
+        for (o3, o4, j1) in candidates {
+            for j1 in [j1, j1 + PI] {
+                let joints = rad_to_joints(&self.calculate_joint_radians(&o3, &o4, target, j1));
+                for joints in turn_variants(&joints, &self.limits) {
+                    let duplicate = results.iter().any(|r: &[f64; 6]| {
+                        r.iter()
+                            .zip(joints.iter())
+                            .all(|(a, b)| (a - b).abs() < 1e-6)
+                    });
+                    if !duplicate {
+                        results.push(joints);
+                    }
+                }
             }
         }
 
-        results.iter().map(|r| rad_to_joints(r)).collect()
+        results
+    }
+
+    /// Determine the FANUC configuration (flip, up, front, and the J1/J4/J6 turn numbers) of a set
+    /// of joint angles, as the controller would display it next to the Cartesian position.
+    ///
+    /// # Arguments
+    ///
+    /// * `joints`: the joint angles in degrees, in the controller convention
+    ///
+    /// returns: Config
+    pub fn config(&self, joints: &[f64; 6]) -> Config {
+        Config::from_chain(joints, &self.fk_all(joints))
     }
 
-    fn calculate_joint_radians(&self, o3: &Point3, o4: &Point3, target: &Frame3) -> [f64; 6] {
+    /// Compute the inverse kinematics for a target tool frame and return the single solution
+    /// which matches a FANUC configuration, the same way the controller resolves a Cartesian
+    /// position recorded with a configuration string. If the turn numbers of the configuration
+    /// put any joint outside of its limits there is no solution.
+    ///
+    /// Because of the non-spherical wrist more than one solution can share the same flip, up,
+    /// and front flags. In that case the first solution found by `ik` is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `target`: the desired position and orientation of the tool frame in relation to the
+    ///   world
+    /// * `config`: the configuration the solution must have
+    ///
+    /// returns: Option<[f64; 6]>
+    pub fn ik_config(&self, target: &Frame3, config: &Config) -> Option<[f64; 6]> {
+        self.ik(target)
+            .into_iter()
+            .find(|j| self.config(j) == *config)
+    }
+
+    fn calculate_joint_radians(
+        &self,
+        o3: &Point3,
+        o4: &Point3,
+        target: &Frame3,
+        j1: f64,
+    ) -> [f64; 6] {
         let mut joints = [0.0; 6];
         let o5 = target * Point3::new(0.0, 0.0, -self.x2);
         let o6 = target * Point3::new(0.0, 0.0, 0.0);
 
-        // Calculate J1 and the frame which brings O3 into the X-Z plane
-        joints[0] = o4.y.atan2(o4.x);
+        // J1 is the frame which brings O3 into the X-Z plane
+        joints[0] = wrap(j1);
         let f1 = Frame3::rotation(Vector3::z() * joints[0]);
         let o3_j1 = f1.inverse() * o3;
 
@@ -303,96 +601,164 @@ impl Crx {
         }
     }
 
-    fn refine_theta(&self, t0: f64, e0: f64, t1: f64, e1: f64, target: &Frame3, o5: &Point3) -> f64 {
-        // println!("t0: {}, e0: {}", t0, e0);
-        // println!("t1: {}, e1: {}", t1, e1);
-        
-        let f = (0.0 - e0) / (e1 - e0);
-        let t = f * (t1 - t0) + t0;
-        // println!("f: {}", f);
-        // println!("t: {}", t);
-        
-        t
-    }
-    
-    pub fn brute_force_o3(&self, target: &Frame3) -> (Vec<f64>, Vec<f64>) {
-        let n = 1500;
+    /// Find every value of theta around the O4 candidate circle at which the upper or lower
+    /// elbow candidate produces a valid solution. The error function is sampled around the
+    /// circle to bracket sign changes and local extrema, and each bracket is then polished to
+    /// machine precision. Extrema which touch zero without crossing it are the tangent (double)
+    /// roots, which a sign change test alone would miss.
+    ///
+    /// Where the candidate circle passes close to the J1 axis the plane of the arm swings around
+    /// very quickly, so the error function changes over a much smaller span of theta than the
+    /// regular sample spacing. Extra samples are packed in around those places.
+    ///
+    /// # Arguments
+    ///
+    /// * `target`: the desired position and orientation of the robot flange
+    ///
+    /// returns: (Vec<f64, Global>, Vec<f64, Global>) for the upper and lower candidates
+    pub fn theta_roots(&self, target: &Frame3) -> (Vec<f64>, Vec<f64>) {
         let o5 = target * Point3::new(0.0, 0.0, -self.x2);
-        let step = 2.0 * std::f64::consts::PI / n as f64;
+        let thetas = self.theta_samples(target);
+
+        let samples = thetas
+            .iter()
+            .map(|&t| (t, self.error(t, target, &o5)))
+            .collect::<Vec<_>>();
+        let upper = samples.iter().map(|(t, e)| (*t, e.0)).collect::<Vec<_>>();
+        let lower = samples.iter().map(|(t, e)| (*t, e.1)).collect::<Vec<_>>();
+
+        (
+            branch_roots(&upper, |t| self.error(t, target, &o5).0),
+            branch_roots(&lower, |t| self.error(t, target, &o5).1),
+        )
+    }
 
-        // There are two places where we may find zeros: (a) crossings, and (b) local minima/maxima.
-        // Local minima/maxima will happen where the derivative is zero, while crossings will
-        // the last value and the next value have different signs.
-        let mut upper_results = Vec::new();
-        let mut lower_results = Vec::new();
-
-        let mut last_theta = 0.0;
-        let (mut last_eu, mut last_el) = self.error(0.0, target, &o5);
-        for i in 0..(n + 1) {
-            let theta = (i + 1) as f64 * step;
-            let (eu, el) = self.error(theta, target, &o5);
-
-            if !eu.is_nan() && !last_eu.is_nan() {
-                if eu.signum() != last_eu.signum() {
-                    upper_results.push(self.refine_theta(last_theta, last_eu, theta, eu, target, &o5));
-                }
+    /// Generate the sorted values of theta in [0, 2pi) at which the IK error function will be
+    /// sampled, refined around the places where the O4 candidate passes near the J1 axis.
+    fn theta_samples(&self, target: &Frame3) -> Vec<f64> {
+        let step = 2.0 * PI / IK_SAMPLES as f64;
+        let mut thetas = (0..IK_SAMPLES).map(|i| i as f64 * step).collect::<Vec<_>>();
+
+        for (tm, rm) in self.axis_approaches(target) {
+            // The arm plane turns through roughly a half circle over a span of theta about as
+            // wide as this, so the samples are spaced geometrically outwards from it. Points
+            // which are on the axis are handled separately.
+            let mut width = rm / self.y1;
+            if width > step || rm < IK_AXIS_TOL {
+                continue;
+            }
 
-                // We'll do minima/maxima later
+            thetas.push(tm);
+            while width < step {
+                thetas.push(tm - width);
+                thetas.push(tm + width);
+                width *= 2.0;
             }
+        }
 
-            if !el.is_nan() && !last_el.is_nan() {
-                if el.signum() != last_el.signum() {
-                    lower_results.push(self.refine_theta(last_theta, last_el, theta, el, target, &o5));
-                }
+        let mut thetas = thetas
+            .into_iter()
+            .map(|t| t.rem_euclid(2.0 * PI))
+            .collect::<Vec<_>>();
+        thetas.sort_by(|a, b| a.total_cmp(b));
+        thetas.dedup();
+        thetas
+    }
+
+    /// The distance from the O4 candidate at `theta` to the J1 axis
+    fn o4_radius(&self, theta: f64, target: &Frame3) -> f64 {
+        let o4 = target * Point3::new(self.y1 * theta.cos(), self.y1 * theta.sin(), -self.x2);
+        o4.x.hypot(o4.y)
+    }
 
-                // We'll do minima/maxima later
+    /// Find the places where the O4 candidate circle comes closest to the J1 axis, returning
+    /// the polished value of theta and the distance to the axis at each one. Only approaches
+    /// which are closer than one regular sample step are returned.
+    fn axis_approaches(&self, target: &Frame3) -> Vec<(f64, f64)> {
+        let step = 2.0 * PI / IK_SAMPLES as f64;
+        let radii = (0..IK_SAMPLES)
+            .map(|i| self.o4_radius(i as f64 * step, target))
+            .collect::<Vec<_>>();
+
+        let mut approaches = Vec::new();
+        for i in 0..IK_SAMPLES {
+            let prev = radii[(i + IK_SAMPLES - 1) % IK_SAMPLES];
+            let next = radii[(i + 1) % IK_SAMPLES];
+            if radii[i] > prev || radii[i] > next {
+                continue;
             }
 
-            last_el = el;
-            last_eu = eu;
-            last_theta = theta;
+            let t = i as f64 * step;
+            let tm = golden_min(|t| self.o4_radius(t, target), t - step, t + step);
+            let rm = self.o4_radius(tm, target);
+            if rm / self.y1 < step {
+                approaches.push((tm.rem_euclid(2.0 * PI), rm));
+            }
         }
 
-        (upper_results, lower_results)
+        approaches
     }
 
-    fn get_candidates(&self, theta: f64, target: &Frame3) -> (Point3, Option<(Point3, Point3)>) {
-        let o4 = target * Point3::new(self.y1 * theta.cos(), self.y1 * theta.sin(), -self.x2);
-        let v0 = -o4.coords;
+    /// Find the values of theta where the O4 candidate lies on the J1 axis (the shoulder
+    /// singularity). At these points the plane of the arm is undefined, and the usual error
+    /// function jumps from one side of the axis to the other.
+    fn axis_crossings(&self, target: &Frame3) -> Vec<(f64, f64)> {
+        self.axis_approaches(target)
+            .into_iter()
+            .filter(|(_, r)| *r < IK_AXIS_TOL)
+            .collect()
+    }
 
-        // Vector from the candidate point's projection on the XY plane to the origin
-        let vp = Vector3::new(v0.x, v0.y, 0.0);
+    /// Solve for the plane of the arm when the O4 candidate at `theta` lies on the J1 axis.
+    /// The elbow candidates sweep a circle around the axis as the plane angle changes, and a
+    /// solution exists wherever the elbow is the correct distance from O5. The lower candidate
+    /// at one plane angle is the upper candidate at the opposite angle, so only the upper
+    /// candidate needs to be searched.
+    ///
+    /// If every plane angle is a solution then J1 and J4 are aligned, and only the single most
+    /// accurate representative of the family is returned.
+    ///
+    /// returns: a list of (O3, O4, plane angle) candidates
+    fn axis_candidates(&self, theta: f64, target: &Frame3) -> Vec<(Point3, Point3, f64)> {
+        let o4 = target * Point3::new(self.y1 * theta.cos(), self.y1 * theta.sin(), -self.x2);
+        let o5 = target * Point3::new(0.0, 0.0, -self.x2);
+        let upper = |phi: f64| {
+            let vp = -Vector3::new(phi.cos(), phi.sin(), 0.0);
+            self.plane_o3s(&o4, &vp).map(|(u, _)| u)
+        };
+        let error = |phi: f64| {
+            upper(phi)
+                .map(|u| (o5 - u).norm() - self.ik_d)
+                .unwrap_or(f64::NAN)
+        };
+
+        let step = 2.0 * PI / IK_SAMPLES as f64;
+        let samples = (0..IK_SAMPLES)
+            .map(|i| (i as f64 * step, error(i as f64 * step)))
+            .collect::<Vec<_>>();
+
+        let mut roots = branch_roots(&samples, error);
+        if samples.iter().all(|(_, e)| e.abs() < IK_FAMILY_TOL) {
+            // Keep only the most accurate member of the family
+            let best = roots
+                .iter()
+                .chain(std::iter::once(&0.0))
+                .min_by(|a, b| error(**a).abs().total_cmp(&error(**b).abs()));
+            roots = best.into_iter().copied().collect();
+        }
 
-        // If the vector from the candidate point projection to the origin is zero
-        // length, it means that the candidate point is directly above the robot origin,
-        // and there's probably some sort of special case
-        let e0 = v0.normalize();
-        let e2 = v0.cross(&vp).normalize();
-        let e1 = e2.cross(&e0).normalize();
+        roots
+            .into_iter()
+            .filter_map(|phi| upper(phi).map(|u| (u, o4, phi)))
+            .collect()
+    }
 
-        if let Some((a, h)) = self.corner_xy(v0.norm()) {
-            let u = e0 * a + e1 * h;
-            let l = e0 * a - e1 * h;
-            (o4, Some((o4 + u, o4 + l)))
-        } else {
-            (o4, None)
-        }
+    fn get_candidates(&self, theta: f64, target: &Frame3) -> (Point3, Option<(Point3, Point3)>) {
+        let o4 = target * Point3::new(self.y1 * theta.cos(), self.y1 * theta.sin(), -self.x2);
+        (o4, self.candidate_o3s(&o4))
     }
 
     pub fn error(&self, theta: f64, target: &Frame3, o5: &Point3) -> (f64, f64) {
-        // let o4 = target * Point3::new(self.y1 * theta.cos(), self.y1 * theta.sin(), -self.x2);
-        // let v0 = -o4.coords;
-        // 
-        // // Vector from the candidate point's projection on the XY plane to the origin
-        // let vp = Vector3::new(v0.x, v0.y, 0.0);
-        // 
-        // // If the vector from the candidate point projection to the origin is zero
-        // // length, it means that the candidate point is directly above the robot origin,
-        // // and there's probably some sort of special case
-        // let e0 = v0.normalize();
-        // let e2 = v0.cross(&vp).normalize();
-        // let e1 = e2.cross(&e0).normalize();
-        
         let (_, o3) = self.get_candidates(theta, target);
 
         if let Some((up, lp)) = o3 {
@@ -405,24 +771,25 @@ impl Crx {
     }
 
     fn candidate_o3s(&self, o4: &Point3) -> Option<(Point3, Point3)> {
+        // Vector from the candidate point's projection on the XY plane to the origin. If this
+        // is zero length the candidate point is directly above the robot origin, the plane of
+        // the arm is undefined, and `axis_candidates` has to be used instead.
+        let vp = Vector3::new(-o4.x, -o4.y, 0.0);
+        self.plane_o3s(o4, &vp)
+    }
+
+    /// Find the upper and lower O3 candidates for an O4 candidate point, given the direction
+    /// `vp` from the O4 point towards the J1 axis which sets the plane of the arm.
+    fn plane_o3s(&self, o4: &Point3, vp: &Vector3) -> Option<(Point3, Point3)> {
         // Vector from the candidate point to the origin
         let v0 = -o4.coords;
 
-        // Vector from the candidate point's projection on the XY plane to the origin
-        let vp = Vector3::new(v0.x, v0.y, 0.0);
-
-        // If the vector from the candidate point projection to the origin is zero
-        // length, it means that the candidate point is directly above the robot origin,
-        // and there's probably some sort of special case
+        // The plane normal is taken from the horizontal direction alone so that the upper and
+        // lower candidates don't swap places when the candidate point crosses the XY plane.
         let e0 = v0.normalize();
-        let e2 = v0.cross(&vp).normalize();
+        let e2 = vp.cross(&Vector3::z()).normalize();
         let e1 = e2.cross(&e0).normalize();
 
-        // let rot_m = Matrix3::from_columns(&[e0, e1, e2]);
-        // let r = UnitQuaternion::from_matrix(&rot_m);
-        // let t = Translation3::from(o4);
-        // let tf = Frame3::from_parts(t, r).inverse();
-
         if let Some((a, h)) = self.corner_xy(v0.norm()) {
             let u = e0 * a + e1 * h;
             let l = e0 * a - e1 * h;
@@ -433,12 +800,251 @@ impl Crx {
     }
 }
 
+/// Find and polish the roots of one branch of the IK error function, given samples `(theta,
+/// error)` sorted around the full circle and a way to evaluate the function at any angle.
+///
+/// Samples which are NaN lie outside the region where the elbow can reach. The valid samples are
+/// split into runs, and the edges of each run are located by bisection and added as extra
+/// points, since roots are often found right at the edge where the elbow straightens out. The
+/// function is periodic, so when every sample is valid the last sample brackets with the first.
+fn branch_roots(samples: &[(f64, f64)], f: impl Fn(f64) -> f64) -> Vec<f64> {
+    let mut roots = Vec::new();
+
+    let runs = if samples.iter().all(|(_, e)| !e.is_nan()) {
+        // Wrap around by one point on each side so every sample has two neighbors
+        let (tf, ef) = samples[0];
+        let (tl, el) = samples[samples.len() - 1];
+        let mut run = vec![(tl - 2.0 * PI, el)];
+        run.extend_from_slice(samples);
+        run.push((tf + 2.0 * PI, ef));
+        vec![run]
+    } else {
+        valid_runs(samples, &f)
+    };
+
+    for run in runs.iter() {
+        for w in run.windows(2) {
+            let (t0, e0) = w[0];
+            let (t1, e1) = w[1];
+            if e0 == 0.0 {
+                roots.push(t0);
+            } else if e1 != 0.0 && e0.signum() != e1.signum() {
+                roots.push(brent(&f, t0, t1, e0, e1));
+            }
+        }
+
+        // A local extremum of the error which moves towards zero may touch it (a tangent root)
+        // or cross it twice between two points without changing sign at either of them. The
+        // first and last points of a run only have one neighbor to compare against.
+        for k in 0..run.len() {
+            let (tp, ep) = run[k.saturating_sub(1)];
+            let (_, e0) = run[k];
+            let (tn, en) = run[(k + 1).min(run.len() - 1)];
+            let sign = e0.signum();
+            if ep.signum() != sign || en.signum() != sign || e0.abs() > ep.abs().min(en.abs()) {
+                continue;
+            }
+
+            let tm = golden_min(|t| sign * f(t), tp, tn);
+            let em = f(tm);
+            if em.signum() != sign {
+                roots.push(brent(&f, tp, tm, ep, em));
+                roots.push(brent(&f, tm, tn, em, en));
+            } else if em.abs() < IK_ROOT_TOL {
+                roots.push(tm);
+            }
+        }
+
+        // The edges of the valid region can be roots themselves
+        for &(t, e) in [run.first(), run.last()].into_iter().flatten() {
+            if e.abs() < IK_ROOT_TOL {
+                roots.push(t);
+            }
+        }
+    }
+
+    // Polishing can land on a discontinuity instead of a root, and adjacent brackets can find
+    // the same root twice
+    let mut unique: Vec<f64> = Vec::new();
+    for t in roots.into_iter().map(|t| t.rem_euclid(2.0 * PI)) {
+        let e = f(t);
+        let duplicate = unique.iter().any(|u| {
+            let d = (u - t).abs();
+            d.min(2.0 * PI - d) < 1e-9
+        });
+        if e.abs() < IK_ROOT_TOL && !duplicate {
+            unique.push(t);
+        }
+    }
+
+    unique
+}
+
+/// Split the samples of one branch of the IK error function into runs of valid (non-NaN)
+/// points, each bounded by the edges of the valid region found by bisection. At least one
+/// sample must be NaN, so every run has an edge at both ends.
+fn valid_runs(samples: &[(f64, f64)], f: &impl Fn(f64) -> f64) -> Vec<Vec<(f64, f64)>> {
+    let n = samples.len();
+    let edge = |valid: f64, invalid: f64| {
+        let (mut valid, mut invalid) = (valid, invalid);
+        for _ in 0..60 {
+            let mid = (valid + invalid) / 2.0;
+            if f(mid).is_nan() {
+                invalid = mid;
+            } else {
+                valid = mid;
+            }
+        }
+        (valid, f(valid))
+    };
+
+    // Start at a NaN sample so that no run wraps around the end of the array, and unwrap the
+    // angles as they pass 2pi so that each run stays in increasing order
+    let first = (0..n).find(|&i| samples[i].1.is_nan()).unwrap_or(0);
+    let at = |k: usize| {
+        let (t, e) = samples[(first + k) % n];
+        let turns = ((first + k) / n) as f64;
+        (t + turns * 2.0 * PI, e)
+    };
+
+    let mut runs = Vec::new();
+    let mut current: Vec<(f64, f64)> = Vec::new();
+
+    for k in 1..=n {
+        let (t, e) = at(k);
+        if e.is_nan() {
+            if let Some(&(tl, _)) = current.last() {
+                current.push(edge(tl, t));
+                runs.push(std::mem::take(&mut current));
+            }
+        } else {
+            if current.is_empty() {
+                current.push(edge(t, at(k - 1).0));
+            }
+            current.push((t, e));
+        }
+    }
+
+    runs
+}
+
+impl Robot for Crx {
+    fn fk(&self, joints: &[f64; 6]) -> Frame3 {
+        Crx::fk(self, joints)
+    }
+
+    fn fk_all(&self, joints: &[f64; 6]) -> [Frame3; 6] {
+        Crx::fk_all(self, joints)
+    }
+
+    fn ik(&self, target: &Frame3) -> Vec<[f64; 6]> {
+        Crx::ik(self, target)
+    }
+
+    fn jacobian(&self, joints: &[f64; 6]) -> Matrix6<f64> {
+        Crx::jacobian(self, joints)
+    }
+
+    fn kinematic_angles(&self, joints: &[f64; 6]) -> [f64; 6] {
+        joints_to_rad(joints)
+    }
+
+    fn tool(&self) -> Frame3 {
+        self.tool
+    }
+
+    fn base(&self) -> Frame3 {
+        self.base
+    }
+
+    fn joint_limits(&self) -> [(f64, f64); 6] {
+        self.limits
+    }
+
+    fn z0(&self) -> f64 {
+        self.z0
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CrxDef {
+    z0: f64,
+    z1: f64,
+    x1: f64,
+    x2: f64,
+    y1: f64,
+
+    #[serde(default)]
+    joint_limits: Option<[(f64, f64); 6]>,
+
+    #[serde(default)]
+    tool: Option<XyzWpr>,
+
+    #[serde(default)]
+    base: Option<XyzWpr>,
+}
+
+#[cfg(feature = "serde")]
+impl From<Crx> for CrxDef {
+    fn from(robot: Crx) -> Self {
+        Self {
+            z0: robot.z0,
+            z1: robot.z1,
+            x1: robot.x1,
+            x2: robot.x2,
+            y1: robot.y1,
+            joint_limits: Some(robot.limits),
+            tool: Some(XyzWpr::from_isometry(&robot.tool)),
+            base: Some(XyzWpr::from_isometry(&robot.base)),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<CrxDef> for Crx {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(def: CrxDef) -> Result<Self> {
+        let mut robot = Crx::from_dimensions(def.z0, def.z1, def.x1, def.x2, def.y1)?;
+        if let Some(limits) = def.joint_limits {
+            robot = robot.with_joint_limits(limits)?;
+        }
+        if let Some(tool) = def.tool {
+            robot.tool = tool.to_isometry();
+        }
+        if let Some(base) = def.base {
+            robot.base = base.to_isometry();
+        }
+        Ok(robot)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::IkSolution;
     use crate::helpers::row_slice_to_iso;
-    use crate::{Point3, Result};
+    use crate::joint_distance;
+    use crate::test_helpers::{assert_jacobian_matches_fk, same_joints};
     use approx::assert_relative_eq;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use test_case::test_case;
+
+    #[test]
+    fn kinematic_angles_couple_j2_j3() {
+        // The forearm turns relative to the upper arm by the sum of J2 and J3
+        let robot = Crx::new_5ia();
+        let joints = [10.0, 20.0, 30.0, 40.0, 50.0, 60.0];
+        let angles = Robot::kinematic_angles(&robot, &joints);
+        let chain = robot.fk_all(&joints);
+
+        let relative = (chain[1].rotation.inverse() * chain[2].rotation).angle();
+        assert!((relative - 50f64.to_radians()).abs() < 1e-12);
+        assert!((angles[2] - 50f64.to_radians()).abs() < 1e-12);
+        assert!((angles[1] - 20f64.to_radians()).abs() < 1e-12);
+    }
 
     #[test]
     fn zero_position() -> Result<()> {
@@ -621,6 +1227,531 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn robot_trait_matches_inherent() {
+        fn flange<R: Robot>(robot: &R, joints: &[f64; 6]) -> Frame3 {
+            robot.fk(joints)
+        }
+
+        let robot = Crx::new_5ia();
+        let j = [10.0, -20.0, 30.0, -40.0, 50.0, -60.0];
+        assert_relative_eq!(flange(&robot, &j), robot.fk(&j), epsilon = 1e-10);
+        assert!(robot.within_limits(&j));
+        assert!(!robot.within_limits(&[0.0, 0.0, 0.0, 0.0, 0.0, 300.0]));
+    }
+
+    #[test]
+    fn ik_verified_residuals() {
+        let robot = Crx::new_5ia();
+        let target = robot.fk(&[10.0, -20.0, 30.0, -40.0, 50.0, -60.0]);
+        let solutions = robot.ik_verified(&target, None);
+        assert_eq!(solutions.len(), robot.ik(&target).len());
+
+        for s in solutions.iter() {
+            assert!(s.within((1e-8, 1e-10)));
+        }
+
+        // Nudging the target away from the solutions makes them fail a tight tolerance
+        let moved = Frame3::translation(0.0, 0.0, 1e-3) * target;
+        let shifted = solutions
+            .iter()
+            .map(|s| IkSolution::verify(&robot, &s.joints, &moved))
+            .collect::<Vec<_>>();
+        assert!(shifted.iter().all(|s| !s.within((1e-6, 1e-6))));
+        assert!(shifted.iter().all(|s| s.within((1.1e-3, 1e-10))));
+    }
+
+    #[test]
+    fn ik_o4_on_axis_discrete() {
+        // The O4 candidate circle passes through the J1 axis, but J1 and J4 are not aligned
+        let robot = Crx::new_5ia();
+        let joints = [0.0, 0.0, 90.0, 0.0, 0.0, 0.0];
+        let target = robot.fk(&joints);
+        let solutions = robot.ik_verified(&target, None);
+
+        assert_eq!(distinct_count(&solutions), 2);
+        assert!(solutions.iter().all(|s| s.within((1e-8, 1e-10))));
+    }
+
+    #[test]
+    fn ik_o4_on_axis_family() {
+        // The forearm is vertical, so J1 and J4 are collinear and any pair with the same
+        // difference between them reaches the target
+        let robot = Crx::new_5ia();
+        let joints = [30.0, 0.0, 90.0, 20.0, 40.0, 10.0];
+        let target = robot.fk(&joints);
+        let solutions = robot.ik_verified(&target, None);
+
+        assert!(solutions.iter().all(|s| s.within((1e-8, 1e-10))));
+        assert!(distinct_count(&solutions) <= 8);
+        // Near the singularity the joints are only resolved to a few micro-degrees
+        let expected = [0.0, 90.0, 40.0, 10.0];
+        assert!(solutions.iter().any(|s| {
+            let j = s.joints;
+            let rest = [j[1], j[2], j[4], j[5]];
+            ((j[0] - j[3] - 10.0 + 180.0).rem_euclid(360.0) - 180.0).abs() < 1e-4
+                && rest
+                    .iter()
+                    .zip(expected.iter())
+                    .all(|(a, b)| (a - b).abs() < 1e-4)
+        }));
+    }
+
+    #[test]
+    fn ik_within_limits() {
+        let robot = Crx::new_5ia();
+        let joints = [10.0, -20.0, 30.0, -40.0, 50.0, -160.0];
+        let solutions = robot.ik(&robot.fk(&joints));
+
+        assert!(solutions.iter().all(|s| robot.within_limits(s)));
+        assert!(
+            solutions
+                .iter()
+                .any(|s| same_joints(s, &joints) && s[5] < -180.0 + 30.0)
+        );
+        assert!(
+            solutions
+                .iter()
+                .any(|s| same_joints(s, &joints) && s[5] > 180.0)
+        );
+    }
+
+    #[test]
+    fn ik_discards_outside_limits() -> Result<()> {
+        let joints = [10.0, -20.0, 30.0, -40.0, 50.0, -60.0];
+        let robot = Crx::new_5ia();
+        let all = robot.ik(&robot.fk(&joints));
+
+        let mut limits = CRX_LIMITS;
+        limits[0] = (0.0, 20.0);
+        let robot = robot.with_joint_limits(limits)?;
+        let limited = robot.ik(&robot.fk(&joints));
+
+        assert!(limited.len() < all.len());
+        assert!(limited.iter().all(|s| s[0] >= 0.0 && s[0] <= 20.0));
+        assert!(limited.iter().any(|s| same_joints(s, &joints)));
+        Ok(())
+    }
+
+    #[test]
+    fn with_joint_limits_rejects_invalid() {
+        let mut limits = CRX_LIMITS;
+        limits[2] = (10.0, -10.0);
+        assert!(Crx::new_5ia().with_joint_limits(limits).is_err());
+
+        limits[2] = (f64::NEG_INFINITY, 10.0);
+        assert!(Crx::new_5ia().with_joint_limits(limits).is_err());
+    }
+
+    #[test]
+    fn ik_closest_to_seed() {
+        let robot = Crx::new_5ia();
+        let joints = [10.0, -20.0, 30.0, -40.0, 50.0, -170.0];
+        let target = robot.fk(&joints);
+
+        let closest = robot.ik_closest(&target, &joints, None);
+        assert_eq!(closest.len(), robot.ik(&target).len());
+        assert!(
+            closest[0]
+                .iter()
+                .zip(joints.iter())
+                .all(|(a, b)| (a - b).abs() < 1e-6)
+        );
+
+        // Seeding near the other turn of J6 selects it instead
+        let seed = [10.0, -20.0, 30.0, -40.0, 50.0, 175.0];
+        let closest = robot.ik_closest(&target, &seed, None);
+        assert!((closest[0][5] - 190.0).abs() < 1e-6);
+
+        // Heavily weighting J6 makes the distance dominated by it
+        let weights = [1.0, 1.0, 1.0, 1.0, 1.0, 1e6];
+        let closest = robot.ik_closest(&target, &seed, Some(&weights));
+        let distances = closest
+            .iter()
+            .map(|j| joint_distance(j, &seed, &weights))
+            .collect::<Vec<_>>();
+        assert!(distances.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn ik_closest_follows_path() {
+        let robot = Crx::new_5ia();
+        let mut current = [0.0, 10.0, -20.0, 30.0, -60.0, 100.0];
+        let start = robot.fk(&current);
+
+        for i in 1..=100 {
+            let target = Frame3::translation(0.0, 2.0 * i as f64, 0.0) * start;
+            let next = robot.ik_closest(&target, &current, None)[0];
+            let step = joint_distance(&next, &current, &[1.0; 6]);
+            assert!(step < 5.0, "jumped {} degrees at step {}", step, i);
+            current = next;
+        }
+    }
+
+    #[test_case([10.0, -20.0, 30.0, -40.0, 50.0, -60.0])]
+    #[test_case([0.0, 0.0, 0.0, 0.0, -90.0, 0.0])]
+    #[test_case([-150.0, 45.0, -100.0, 170.0, 5.0, 200.0])]
+    fn jacobian_matches_finite_difference(joints: [f64; 6]) {
+        assert_jacobian_matches_fk(&Crx::new_5ia(), &joints);
+    }
+
+    #[test]
+    fn jacobian_wpr_matches_finite_difference() {
+        let robot = Crx::new_5ia();
+        let joints = [10.0, -20.0, 30.0, -40.0, 50.0, -60.0];
+        let jac = robot.jacobian_wpr(&joints).unwrap();
+        let (w0, p0, r0) = robot.fk(&joints).rotation.euler_angles();
+
+        let step: f64 = 1e-6;
+        for i in 0..6 {
+            let mut moved = joints;
+            moved[i] += step.to_degrees();
+            let (w, p, r) = robot.fk(&moved).rotation.euler_angles();
+            let rates = Vector3::new(w - w0, p - p0, r - r0) / step;
+            assert_relative_eq!(
+                jac.fixed_view::<3, 1>(3, i).clone_owned(),
+                rates,
+                epsilon = 1e-6
+            );
+        }
+
+        // The linear rows are the same as the geometric Jacobian
+        let geometric = robot.jacobian(&joints);
+        assert_relative_eq!(
+            jac.fixed_view::<3, 6>(0, 0).clone_owned(),
+            geometric.fixed_view::<3, 6>(0, 0).clone_owned()
+        );
+    }
+
+    #[test]
+    fn jacobian_wpr_gimbal_lock() {
+        // The flange pointing straight along the base X axis puts P at 90 degrees
+        let robot = Crx::new_5ia();
+        let joints = [0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+        let p = robot.fk(&joints).rotation.euler_angles().1;
+        assert_relative_eq!(p.abs(), PI / 2.0, epsilon = 1e-9);
+        assert!(robot.jacobian_wpr(&joints).is_none());
+    }
+
+    fn gripper() -> Frame3 {
+        Frame3::from_parts(
+            Translation::<f64, 3>::new(10.0, -20.0, 150.0),
+            UnitQuaternion::from_euler_angles(0.1, -0.2, 0.3),
+        )
+    }
+
+    #[test]
+    fn fk_with_tool() {
+        let joints = [10.0, -20.0, 30.0, -40.0, 50.0, -60.0];
+        let flange = Crx::new_5ia().fk(&joints);
+        let robot = Crx::new_5ia().with_tool(gripper());
+
+        assert_relative_eq!(robot.fk(&joints), flange * gripper(), epsilon = 1e-10);
+        assert_relative_eq!(robot.fk_all(&joints)[5], flange, epsilon = 1e-10);
+    }
+
+    #[test]
+    fn ik_with_tool() {
+        let joints = [10.0, -20.0, 30.0, -40.0, 50.0, -60.0];
+        let robot = Crx::new_5ia().with_tool(gripper());
+        let target = robot.fk(&joints);
+
+        let solutions = robot.ik(&target);
+        assert!(solutions.iter().any(|s| same_joints(s, &joints)));
+        for s in solutions.iter() {
+            assert_relative_eq!(robot.fk(s), target, epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn per_call_tool_matches_attached() {
+        let joints = [10.0, -20.0, 30.0, -40.0, 50.0, -60.0];
+        let attached = Crx::new_5ia().with_tool(gripper());
+        let mut robot = Crx::new_5ia();
+
+        let target = robot.fk_with_tool(&joints, &gripper());
+        assert_relative_eq!(target, attached.fk(&joints), epsilon = 1e-10);
+        let expected = attached.ik(&target);
+
+        // Switching the attached tool and then asking for a different one per call
+        for tool in [Frame3::identity(), Frame3::translation(0.0, 0.0, 50.0)] {
+            robot.set_tool(tool);
+            let solutions = robot.ik_with_tool(&target, &gripper());
+            assert_eq!(solutions.len(), expected.len());
+            for (a, b) in solutions.iter().zip(expected.iter()) {
+                assert!(a.iter().zip(b.iter()).all(|(x, y)| (x - y).abs() < 1e-8));
+            }
+        }
+    }
+
+    #[test]
+    fn jacobian_with_tool() {
+        let robot = Crx::new_5ia().with_tool(gripper());
+        assert_jacobian_matches_fk(&robot, &[10.0, -20.0, 30.0, -40.0, 50.0, -60.0]);
+    }
+
+    /// A robot hanging upside down from a tilted bracket
+    fn bracket() -> Frame3 {
+        Frame3::from_parts(
+            Translation::<f64, 3>::new(1000.0, -500.0, 2000.0),
+            UnitQuaternion::from_euler_angles(PI - 0.3, 0.2, 0.5),
+        )
+    }
+
+    #[test]
+    fn mount_offset_by_z0() {
+        let robot = Crx::new_10ia().with_mount(bracket());
+        let expected = bracket() * Frame3::translation(0.0, 0.0, robot.z0());
+
+        assert_relative_eq!(robot.base(), expected, epsilon = 1e-10);
+        assert_relative_eq!(robot.mount(), bracket(), epsilon = 1e-10);
+    }
+
+    #[test]
+    fn fk_with_base() {
+        let nominal = Crx::new_5ia().with_tool(gripper());
+        let robot = Crx::new_5ia().with_tool(gripper()).with_base(bracket());
+        let joints = [10.0, -20.0, 30.0, -40.0, 50.0, -60.0];
+
+        assert_relative_eq!(
+            robot.fk(&joints),
+            bracket() * nominal.fk(&joints),
+            epsilon = 1e-9
+        );
+        for (a, b) in robot.fk_all(&joints).iter().zip(nominal.fk_all(&joints)) {
+            assert_relative_eq!(*a, bracket() * b, epsilon = 1e-9);
+        }
+        assert_eq!(robot.config(&joints), nominal.config(&joints));
+    }
+
+    #[test]
+    fn ik_with_base() {
+        let robot = Crx::new_5ia().with_tool(gripper()).with_mount(bracket());
+        let joints = [10.0, -20.0, 30.0, -40.0, 50.0, -60.0];
+        let target = robot.fk(&joints);
+
+        let solutions = robot.ik_verified(&target, None);
+        assert!(solutions.iter().all(|s| s.within((1e-6, 1e-9))));
+        assert!(solutions.iter().any(|s| same_joints(&s.joints, &joints)));
+    }
+
+    #[test]
+    fn jacobian_with_base() {
+        let robot = Crx::new_5ia().with_tool(gripper()).with_base(bracket());
+        assert_jacobian_matches_fk(&robot, &[10.0, -20.0, 30.0, -40.0, 50.0, -60.0]);
+    }
+
+    #[test]
+    fn user_frame_round_trip() {
+        let robot = Crx::new_5ia().with_base(bracket());
+        let user_frame = bracket()
+            * Frame3::from_parts(
+                Translation::<f64, 3>::new(400.0, 100.0, 300.0),
+                UnitQuaternion::from_euler_angles(PI, 0.0, 0.4),
+            );
+        let joints = [10.0, -20.0, 30.0, -40.0, 50.0, -60.0];
+
+        let local = robot.fk_in(&joints, &user_frame);
+        assert_relative_eq!(user_frame * local, robot.fk(&joints), epsilon = 1e-9);
+
+        let solutions = robot.ik_in(&local, &user_frame);
+        assert!(solutions.iter().any(|s| same_joints(s, &joints)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() -> Result<()> {
+        let robot = Crx::new_10ia().with_tool(gripper()).with_mount(bracket());
+        let text = serde_json::to_string(&robot)?;
+        let loaded: Crx = serde_json::from_str(&text)?;
+
+        let joints = [10.0, -20.0, 30.0, -40.0, 50.0, -60.0];
+        assert_relative_eq!(loaded.fk(&joints), robot.fk(&joints), epsilon = 1e-9);
+        assert_eq!(loaded.joint_limits(), robot.joint_limits());
+        assert_eq!(loaded.z0(), robot.z0());
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_defaults_and_validation() -> Result<()> {
+        let text = r#"{"z0": 185.0, "z1": 410.0, "x1": 430.0, "x2": 145.0, "y1": 130.0}"#;
+        let loaded: Crx = serde_json::from_str(text)?;
+        let joints = [10.0, -20.0, 30.0, -40.0, 50.0, -60.0];
+        assert_relative_eq!(
+            loaded.fk(&joints),
+            Crx::new_5ia().fk(&joints),
+            epsilon = 1e-12
+        );
+        assert_eq!(loaded.joint_limits(), CRX_LIMITS);
+
+        let text = r#"{"z0": 185.0, "z1": 410.0, "x1": 430.0, "x2": 145.0, "y1": 0.0}"#;
+        assert!(serde_json::from_str::<Crx>(text).is_err());
+        Ok(())
+    }
+
+    /// Count the solutions which are different from each other by more than whole turns
+    fn distinct_count(solutions: &[IkSolution]) -> usize {
+        let mut distinct: Vec<[f64; 6]> = Vec::new();
+        for s in solutions {
+            if !distinct.iter().any(|d| same_joints(d, &s.joints)) {
+                distinct.push(s.joints);
+            }
+        }
+        distinct.len()
+    }
+
+    #[test]
+    fn from_dimensions_matches_datasheet() -> Result<()> {
+        let robot = Crx::from_dimensions(185.0, 410.0, 430.0, 145.0, 130.0)?;
+        let nominal = Crx::new_5ia();
+        let j = [10.0, -20.0, 30.0, -40.0, 50.0, -60.0];
+        assert_relative_eq!(robot.fk(&j), nominal.fk(&j), epsilon = 1e-10);
+        Ok(())
+    }
+
+    #[test]
+    fn from_dimensions_rejects_degenerate() {
+        assert!(Crx::from_dimensions(185.0, 0.0, 430.0, 145.0, 130.0).is_err());
+        assert!(Crx::from_dimensions(185.0, 410.0, -430.0, 145.0, 130.0).is_err());
+        assert!(Crx::from_dimensions(185.0, 410.0, 430.0, -1.0, 130.0).is_err());
+        assert!(Crx::from_dimensions(185.0, 410.0, 430.0, 145.0, 0.0).is_err());
+        assert!(Crx::from_dimensions(f64::NAN, 410.0, 430.0, 145.0, 130.0).is_err());
+    }
+
+    #[test]
+    fn branch_roots_tangent() {
+        // 1 - cos(t - 1) touches zero at t = 1 without ever changing sign
+        let f = |t: f64| 1.0 - (t - 1.0).cos();
+        let samples = (0..IK_SAMPLES)
+            .map(|i| i as f64 * 2.0 * PI / IK_SAMPLES as f64)
+            .map(|t| (t, f(t)))
+            .collect::<Vec<_>>();
+
+        let roots = branch_roots(&samples, f);
+        assert_eq!(roots.len(), 1);
+        assert_relative_eq!(roots[0], 1.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn branch_roots_between_samples() {
+        // Dips below zero between two samples, crossing twice very close together
+        let f = |t: f64| (t - 1.0).powi(2) - 1e-8;
+        let samples = [0.0, 0.9, 1.1, 2.0].map(|t| (t, f(t)));
+
+        let mut roots = branch_roots(&samples, f);
+        roots.sort_by(|a, b| a.total_cmp(b));
+        assert_eq!(roots.len(), 2);
+        assert_relative_eq!(roots[0], 1.0 - 1e-4, epsilon = 1e-12);
+        assert_relative_eq!(roots[1], 1.0 + 1e-4, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn crx5ia_ik_bulk() -> Result<()> {
+        let bytes = include_bytes!("test_data/fanuc_crx_5ia.json");
+        let data: Vec<([f64; 6], [f64; 16])> = serde_json::from_slice(bytes)?;
+
+        let robot = Crx::new_5ia();
+        for (joints, expected) in data {
+            let expected = row_slice_to_iso(&expected)?;
+            let solutions = robot.ik(&expected);
+
+            for solution in solutions.iter() {
+                assert_relative_eq!(robot.fk(solution), expected, epsilon = 1e-8);
+            }
+
+            let found = solutions.iter().any(|s| same_joints(s, &joints));
+            assert!(found, "{:?} not found in IK solutions", joints);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn crx10ia_ik_bulk() -> Result<()> {
+        let bytes = include_bytes!("test_data/fanuc_crx_10ia.json");
+        let data: Vec<([f64; 6], [f64; 16])> = serde_json::from_slice(bytes)?;
+
+        let robot = Crx::new_10ia();
+        for (joints, expected) in data {
+            let expected = row_slice_to_iso(&expected)?;
+            let solutions = robot.ik(&expected);
+
+            for solution in solutions.iter() {
+                assert_relative_eq!(robot.fk(solution), expected, epsilon = 1e-8);
+            }
+
+            let found = solutions.iter().any(|s| same_joints(s, &joints));
+            assert!(found, "{:?} not found in IK solutions", joints);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn ik_j3_turn() {
+        // The kinematic J3 angle here is 214.2 degrees, which wraps to -145.8 and so gives -282.9
+        // in the controller convention until J3 is moved a whole turn by `turn_variants`
+        let robot = Crx::new_10ia();
+        let joints = [-30.4, 137.1, 77.1, 129.6, -125.5, 114.9];
+        let solutions = robot.ik(&robot.fk(&joints));
+
+        let found = solutions.iter().any(|s| {
+            s.iter()
+                .zip(joints.iter())
+                .all(|(a, b)| (a - b).abs() < 1e-6)
+        });
+        assert!(found, "{:?} not found in IK solutions", joints);
+        assert!(solutions.iter().all(|s| robot.within_limits(s)));
+    }
+
+    #[test]
+    fn config_home() {
+        let robot = Crx::new_5ia();
+        let config = robot.config(&[0.0, 0.0, 0.0, 0.0, -90.0, 0.0]);
+        assert_eq!(config.to_string(), "N U T, 0, 0, 0");
+
+        let config = robot.config(&[190.0, 0.0, 0.0, -200.0, 90.0, 0.0]);
+        assert_eq!(config.to_string(), "F U T, 1, -1, 0");
+    }
+
+    #[test]
+    fn ik_config_bulk() -> Result<()> {
+        let bytes = include_bytes!("test_data/fanuc_crx_5ia.json");
+        let data: Vec<([f64; 6], [f64; 16])> = serde_json::from_slice(bytes)?;
+
+        let robot = Crx::new_5ia();
+        for (joints, expected) in data.iter() {
+            let expected = row_slice_to_iso(expected)?;
+            let config = robot.config(joints);
+            let Some(solution) = robot.ik_config(&expected, &config) else {
+                // Some of the controller data is outside of the joint limits
+                assert!(!robot.within_limits(joints));
+                continue;
+            };
+            assert_relative_eq!(robot.fk(&solution), expected, epsilon = 1e-6);
+            assert_eq!(robot.config(&solution), config);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn ik_config_turns() {
+        let robot = Crx::new_5ia();
+        let joints = [-170.0, 20.0, 30.0, -185.0, 40.0, 200.0];
+        let config = robot.config(&joints);
+        assert_eq!(config.turns, [0, -1, 1]);
+
+        let solution = robot.ik_config(&robot.fk(&joints), &config).unwrap();
+        assert!(solution[3] < -180.0 && solution[5] > 180.0);
+        assert_eq!(robot.config(&solution), config);
+
+        // J1 has no room for a second turn
+        let config = Config::new(config.flip, config.up, config.front, [1, -1, 1]);
+        assert!(robot.ik_config(&robot.fk(&joints), &config).is_none());
+    }
+
     #[test]
     fn crx5ia_bulk() -> Result<()> {
         let bytes = include_bytes!("test_data/fanuc_crx_5ia.json");
@@ -650,4 +1781,28 @@ mod tests {
 
         Ok(())
     }
+
+    /// There is no controller data for these models yet, so this only checks that the IK solver
+    /// handles their link lengths, not that the link lengths are right
+    #[test_case(Crx::new_10ia_l(); "10ia_l")]
+    #[test_case(Crx::new_20ia_l(); "20ia_l")]
+    #[test_case(Crx::new_25ia(); "25ia")]
+    #[test_case(Crx::new_30ia(); "30ia")]
+    fn ik_round_trip_sampled(robot: Crx) {
+        let mut rng = StdRng::seed_from_u64(25);
+        let limits = robot.joint_limits();
+        for _ in 0..100 {
+            let joints: [f64; 6] =
+                std::array::from_fn(|i| rng.random_range(limits[i].0..limits[i].1));
+            let expected = robot.fk(&joints);
+            let solutions = robot.ik(&expected);
+
+            for solution in solutions.iter() {
+                assert_relative_eq!(robot.fk(solution), expected, epsilon = 1e-6);
+            }
+
+            let found = solutions.iter().any(|s| same_joints(s, &joints));
+            assert!(found, "{:?} not found in IK solutions", joints);
+        }
+    }
 }
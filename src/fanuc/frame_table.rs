@@ -0,0 +1,262 @@
+//! Numbered frame tables matching the way the FANUC controller stores tool (UTOOL) and user
+//! (UFRAME) frames. Frame number zero is reserved by the controller for the identity frame (the
+//! flange for tools, the world for user frames) and can't be changed, while the remaining
+//! numbers start out as the identity until they are set. Each numbered frame can also be given a
+//! name, which is stored as the frame's comment on the controller.
+//!
+//! With the `serde` feature a table is serialized as a list of frames in number order, each as
+//! its XYZ and WPR values (in mm and degrees) along with an optional name:
+//!
+//! ```json
+//! { "frames": [ { "x": 0.0, "y": 0.0, "z": 150.0, "w": 0.0, "p": 0.0, "r": 0.0, "name": "gripper" } ] }
+//! ```
+
+use crate::{Frame3, Result};
+
+#[cfg(feature = "serde")]
+use crate::XyzWpr;
+
+/// The number of tool frames available on a standard FANUC controller
+pub const UTOOL_COUNT: usize = 10;
+
+/// The number of user frames available on a standard FANUC controller
+pub const UFRAME_COUNT: usize = 9;
+
+/// A numbered table of frames, such as the UTOOL or UFRAME table of a FANUC controller
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "FrameTableDef", into = "FrameTableDef")
+)]
+pub struct FrameTable {
+    frames: Vec<Frame3>,
+    names: Vec<String>,
+}
+
+impl FrameTable {
+    /// Create a table with frame numbers from 1 to `count`, all set to the identity
+    pub fn new(count: usize) -> Self {
+        Self {
+            frames: vec![Frame3::identity(); count],
+            names: vec![String::new(); count],
+        }
+    }
+
+    /// Create a table sized like the UTOOL table of a standard FANUC controller
+    pub fn utool() -> Self {
+        Self::new(UTOOL_COUNT)
+    }
+
+    /// Create a table sized like the UFRAME table of a standard FANUC controller
+    pub fn uframe() -> Self {
+        Self::new(UFRAME_COUNT)
+    }
+
+    /// The highest frame number in the table
+    pub fn count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Get a frame by number, where frame zero is always the identity. Returns `None` if the
+    /// number is beyond the end of the table.
+    pub fn get(&self, number: usize) -> Option<Frame3> {
+        match number {
+            0 => Some(Frame3::identity()),
+            n => self.frames.get(n - 1).copied(),
+        }
+    }
+
+    /// Set a frame by number. Frame zero can't be set, the same as on the controller.
+    ///
+    /// # Arguments
+    ///
+    /// * `number`: the frame number, from 1 to the size of the table
+    /// * `frame`: the frame, relative to the flange for tool frames or to the world for user
+    ///   frames. A user frame copied from a controller is relative to the robot origin, and must
+    ///   be premultiplied by the robot's base if one is set.
+    ///
+    /// returns: Result<(), Box<dyn Error, Global>>
+    pub fn set(&mut self, number: usize, frame: Frame3) -> Result<()> {
+        let index = self.index(number)?;
+        self.frames[index] = frame;
+        Ok(())
+    }
+
+    /// Set a frame by number along with its name, the same as setting the frame and its comment
+    /// on the controller.
+    ///
+    /// # Arguments
+    ///
+    /// * `number`: the frame number, from 1 to the size of the table
+    /// * `name`: the name of the frame, such as `"fixture_a"`
+    /// * `frame`: the frame, the same as for `set`
+    ///
+    /// returns: Result<(), Box<dyn Error, Global>>
+    pub fn set_named(&mut self, number: usize, name: &str, frame: Frame3) -> Result<()> {
+        let index = self.index(number)?;
+        self.frames[index] = frame;
+        self.names[index] = name.to_string();
+        Ok(())
+    }
+
+    /// Get the name of a frame by number. Frame zero and frames which haven't been named have no
+    /// name.
+    pub fn name(&self, number: usize) -> Option<&str> {
+        let index = number.checked_sub(1)?;
+        self.names
+            .get(index)
+            .map(|n| n.as_str())
+            .filter(|n| !n.is_empty())
+    }
+
+    /// Find the number of a frame by its name, returning the lowest number if more than one
+    /// frame has the same name
+    pub fn number_of(&self, name: &str) -> Option<usize> {
+        if name.is_empty() {
+            return None;
+        }
+        self.names.iter().position(|n| n == name).map(|i| i + 1)
+    }
+
+    /// Get a frame by its name
+    pub fn get_named(&self, name: &str) -> Option<Frame3> {
+        self.number_of(name).and_then(|n| self.get(n))
+    }
+
+    fn index(&self, number: usize) -> Result<usize> {
+        if number == 0 || number > self.frames.len() {
+            return Err(format!(
+                "Frame number {} is outside of the range 1 to {}",
+                number,
+                self.frames.len()
+            )
+            .into());
+        }
+
+        Ok(number - 1)
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct FrameTableDef {
+    frames: Vec<FrameDef>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct FrameDef {
+    #[serde(flatten)]
+    pose: XyzWpr,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    name: String,
+}
+
+#[cfg(feature = "serde")]
+impl From<FrameTable> for FrameTableDef {
+    fn from(table: FrameTable) -> Self {
+        let frames = table
+            .frames
+            .iter()
+            .zip(table.names)
+            .map(|(f, name)| FrameDef {
+                pose: XyzWpr::from_isometry(f),
+                name,
+            })
+            .collect();
+        Self { frames }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<FrameTableDef> for FrameTable {
+    fn from(def: FrameTableDef) -> Self {
+        let (frames, names) = def
+            .frames
+            .into_iter()
+            .map(|f| (f.pose.to_isometry(), f.name))
+            .unzip();
+        Self { frames, names }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn zero_is_identity() -> Result<()> {
+        let mut table = FrameTable::utool();
+        table.set(1, Frame3::translation(0.0, 0.0, 100.0))?;
+
+        assert_eq!(table.get(0), Some(Frame3::identity()));
+        assert!(table.set(0, Frame3::translation(1.0, 0.0, 0.0)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn numbered_frames() -> Result<()> {
+        let mut table = FrameTable::utool();
+        table.set(10, Frame3::translation(0.0, 0.0, 100.0))?;
+
+        assert_relative_eq!(table.get(10).unwrap(), Frame3::translation(0.0, 0.0, 100.0));
+        assert_eq!(table.get(2), Some(Frame3::identity()));
+        assert_eq!(table.get(11), None);
+        assert!(table.set(11, Frame3::identity()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn named_frames() -> Result<()> {
+        let mut table = FrameTable::uframe();
+        table.set_named(3, "fixture_a", Frame3::translation(500.0, 0.0, 0.0))?;
+        table.set(4, Frame3::translation(0.0, 500.0, 0.0))?;
+
+        assert_eq!(table.name(3), Some("fixture_a"));
+        assert_eq!(table.name(4), None);
+        assert_eq!(table.name(0), None);
+        assert_eq!(table.number_of("fixture_a"), Some(3));
+        assert_eq!(table.number_of(""), None);
+        assert_relative_eq!(
+            table.get_named("fixture_a").unwrap(),
+            Frame3::translation(500.0, 0.0, 0.0)
+        );
+        assert_eq!(table.get_named("fixture_b"), None);
+        assert!(table.set_named(0, "world", Frame3::identity()).is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() -> Result<()> {
+        let mut table = FrameTable::utool();
+        table.set_named(
+            1,
+            "gripper",
+            XyzWpr::new(0.0, 0.0, 150.0, 0.0, 0.0, 0.0).to_isometry(),
+        )?;
+        table.set(
+            2,
+            XyzWpr::new(10.0, -20.0, 30.0, 40.0, -50.0, 60.0).to_isometry(),
+        )?;
+
+        let text = serde_json::to_string(&table)?;
+        assert!(text.starts_with(
+            r#"{"frames":[{"x":0.0,"y":0.0,"z":150.0,"w":0.0,"p":0.0,"r":0.0,"name":"gripper"},"#
+        ));
+
+        let loaded: FrameTable = serde_json::from_str(&text)?;
+        assert_eq!(loaded.count(), UTOOL_COUNT);
+        assert_eq!(loaded.name(1), Some("gripper"));
+        assert_eq!(loaded.name(2), None);
+        assert_relative_eq!(
+            loaded.get(2).unwrap(),
+            table.get(2).unwrap(),
+            epsilon = 1e-9
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,735 @@
+//! Module for the LR Mate 200iD series of small robots. These are conventional 6-axis arms with
+//! a spherical wrist, which means that the position of the wrist center is determined entirely
+//! by the first three joints and the orientation of the flange by the last three.  This allows
+//! the inverse kinematics to be solved in closed form with up to eight solutions.
+//!
+//! Like the CRX series, the LR Mate has the linked J2/J3 quirk common to FANUC robots, so the
+//! J3 angle in the controller is measured relative to the horizontal rather than relative to
+//! the upper arm.
+
+use crate::fanuc::{
+    Config, check_limits, end_adjust, jacobian, joints_to_rad, rad_to_joints, turn_variants, wrap,
+};
+use crate::nalgebra::{Matrix6, Translation, UnitQuaternion};
+use crate::type_aliases::Frame3;
+use crate::{Point3, Result, Robot, Vector3};
+use std::f64::consts::PI;
+
+#[cfg(feature = "serde")]
+use crate::XyzWpr;
+
+/// Joint limits in degrees (controller convention) from the LR Mate 200iD datasheets
+const LR_MATE_LIMITS: [(f64, f64); 6] = [
+    (-170.0, 170.0),
+    (-100.0, 145.0),
+    (-215.0, 215.0),
+    (-190.0, 190.0),
+    (-125.0, 125.0),
+    (-360.0, 360.0),
+];
+
+/// A robot from the FANUC LR Mate 200iD series.
+///
+/// With the `serde` feature the robot is serialized by its link dimensions in mm, its joint
+/// limits in degrees, and its tool and base frames as XYZ and WPR values. The joint limits,
+/// tool, and base are optional when deserializing, and default to the datasheet limits and
+/// identity frames.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "LrMateDef", try_from = "LrMateDef")
+)]
+pub struct LrMate {
+    z0: f64,
+    x0: f64,
+    z1: f64,
+    z2: f64,
+    x1: f64,
+    x2: f64,
+    h: [Vector3; 6],
+    limits: [(f64, f64); 6],
+    tool: Frame3,
+    base: Frame3,
+}
+
+impl LrMate {
+    /// The height from the bottom of the mounting flange to the world origin, which sits at the
+    /// height of the J2 axis. This doesn't affect the kinematics, but is a necessary value from
+    /// the datasheet for doing layout and position studies.
+    pub fn z0(&self) -> f64 {
+        self.z0
+    }
+
+    /// The offset from the J1 axis to the J2 axis
+    pub fn x0(&self) -> f64 {
+        self.x0
+    }
+
+    /// The height from the J2 axis to the J3 axis
+    pub fn z1(&self) -> f64 {
+        self.z1
+    }
+
+    /// The height from the J3 axis to the J4 axis
+    pub fn z2(&self) -> f64 {
+        self.z2
+    }
+
+    /// The length from the J3 axis to the wrist center (the J5 axis)
+    pub fn x1(&self) -> f64 {
+        self.x1
+    }
+
+    /// The length from the wrist center to the robot flange
+    pub fn x2(&self) -> f64 {
+        self.x2
+    }
+
+    /// The lower and upper limits of each joint in degrees, as they would be displayed in the
+    /// FANUC controller.
+    pub fn joint_limits(&self) -> [(f64, f64); 6] {
+        self.limits
+    }
+
+    /// The tool frame (the FANUC UTOOL) relative to the flange. Forward kinematics return the
+    /// pose of this frame, and inverse kinematics targets are poses for it. With no tool set this
+    /// is the identity, and both work directly with the flange.
+    pub fn tool(&self) -> Frame3 {
+        self.tool
+    }
+
+    /// Set the tool frame (the FANUC UTOOL) relative to the flange, such as the TCP of a gripper
+    pub fn set_tool(&mut self, tool: Frame3) {
+        self.tool = tool;
+    }
+
+    /// Return the robot with a tool frame (the FANUC UTOOL) attached relative to the flange
+    pub fn with_tool(mut self, tool: Frame3) -> Self {
+        self.tool = tool;
+        self
+    }
+
+    /// The pose of the robot origin in the world. Forward kinematics return poses in the world,
+    /// and inverse kinematics targets are poses in the world. With no base set this is the
+    /// identity, and the world is the robot origin, the same as on the controller.
+    pub fn base(&self) -> Frame3 {
+        self.base
+    }
+
+    /// Set the pose of the robot origin in the world
+    pub fn set_base(&mut self, base: Frame3) {
+        self.base = base;
+    }
+
+    /// Return the robot with its origin placed at a pose in the world
+    pub fn with_base(mut self, base: Frame3) -> Self {
+        self.base = base;
+        self
+    }
+
+    /// Place the robot by the bottom of its mounting flange, such as on the top surface of a
+    /// table, a rail carriage, or an angled bracket. The robot origin sits `z0` above the
+    /// mounting flange along its Z axis.
+    pub fn set_mount(&mut self, mount: Frame3) {
+        self.base = mount * Frame3::translation(0.0, 0.0, self.z0);
+    }
+
+    /// Return the robot placed by the bottom of its mounting flange. See `set_mount`.
+    pub fn with_mount(mut self, mount: Frame3) -> Self {
+        self.set_mount(mount);
+        self
+    }
+
+    /// Internal constructor for the LR Mate 200iD series of robots.
+    ///
+    /// # Arguments
+    ///
+    /// * `z0`: The height from the bottom of the mounting flange to the world origin (330mm on
+    ///   the LR Mate 200iD datasheet).
+    /// * `x0`: The offset from the J1 axis to the J2 axis (50mm on the LR Mate 200iD datasheet).
+    /// * `z1`: The height from the J2 axis to the J3 axis (330mm on the LR Mate 200iD datasheet).
+    /// * `z2`: The height from the J3 axis to the J4 axis (35mm on the LR Mate 200iD datasheet).
+    /// * `x1`: The length from the J3 axis to the wrist center (335mm on the LR Mate 200iD
+    ///   datasheet).
+    /// * `x2`: The length from the wrist center to the robot flange (80mm on the LR Mate 200iD
+    ///   datasheet).
+    ///
+    /// returns: LrMate
+    fn new(z0: f64, x0: f64, z1: f64, z2: f64, x1: f64, x2: f64) -> Self {
+        // The h vectors are the directions of the rotation axes associated with each joint.
+        let h = [
+            Vector3::z(),
+            Vector3::y(),
+            -Vector3::y(),
+            -Vector3::x(),
+            -Vector3::y(),
+            -Vector3::x(),
+        ];
+
+        Self {
+            z0,
+            x0,
+            z1,
+            z2,
+            x1,
+            x2,
+            h,
+            limits: LR_MATE_LIMITS,
+            tool: Frame3::identity(),
+            base: Frame3::identity(),
+        }
+    }
+
+    /// Creates an LR Mate-like robot from user supplied link dimensions, such as those identified
+    /// by calibrating a real robot. An error is returned for any value which is not finite or
+    /// which would collapse part of the arm.
+    ///
+    /// # Arguments
+    ///
+    /// * `z0`: The height from the bottom of the mounting flange to the world origin, must not
+    ///   be negative.
+    /// * `x0`: The offset from the J1 axis to the J2 axis, must not be negative.
+    /// * `z1`: The height from the J2 axis to the J3 axis, must be positive.
+    /// * `z2`: The height from the J3 axis to the J4 axis, must not be negative.
+    /// * `x1`: The length from the J3 axis to the wrist center, must be positive.
+    /// * `x2`: The length from the wrist center to the robot flange, must not be negative.
+    ///
+    /// returns: Result<LrMate, Box<dyn Error, Global>>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use industrial_robots::fanuc::LrMate;
+    ///
+    /// let robot = LrMate::from_dimensions(330.0, 50.0, 330.2, 35.0, 334.9, 80.0).unwrap();
+    /// assert!(LrMate::from_dimensions(330.0, 50.0, 0.0, 35.0, 335.0, 80.0).is_err());
+    /// ```
+    pub fn from_dimensions(z0: f64, x0: f64, z1: f64, z2: f64, x1: f64, x2: f64) -> Result<Self> {
+        let values = [
+            ("z0", z0),
+            ("x0", x0),
+            ("z1", z1),
+            ("z2", z2),
+            ("x1", x1),
+            ("x2", x2),
+        ];
+        if let Some((name, _)) = values.iter().find(|(_, v)| !v.is_finite()) {
+            return Err(format!("LR Mate dimension {} must be a finite value", name).into());
+        }
+
+        if z0 < 0.0 || x0 < 0.0 || z2 < 0.0 || x2 < 0.0 {
+            return Err("LR Mate dimensions z0, x0, z2, and x2 must not be negative".into());
+        }
+
+        // The upper arm and forearm are the two sides of the triangle which places the elbow,
+        // so if either is zero the elbow is undefined
+        if z1 <= 0.0 || x1 <= 0.0 {
+            return Err("LR Mate dimensions z1 and x1 must be positive".into());
+        }
+
+        Ok(Self::new(z0, x0, z1, z2, x1, x2))
+    }
+
+    /// Replace the joint limits of the robot, for example to match the software limits set on a
+    /// particular controller. Solutions from `ik` are expanded and filtered against these
+    /// limits.
+    ///
+    /// # Arguments
+    ///
+    /// * `limits`: the lower and upper limit of each joint in degrees, in the controller
+    ///   convention
+    ///
+    /// returns: Result<LrMate, Box<dyn Error, Global>>
+    pub fn with_joint_limits(mut self, limits: [(f64, f64); 6]) -> Result<Self> {
+        check_limits(&limits)?;
+        self.limits = limits;
+        Ok(self)
+    }
+
+    /// Creates a new LR Mate 200iD robot. The dimensions are from the arm dimension drawing in
+    /// FANUC's LR Mate 200iD datasheet, and agree with the ROS-Industrial
+    /// `fanuc_lrmate200id_support` package. They have not been checked against a controller.
+    pub fn new_200id() -> Self {
+        Self::new(330.0, 50.0, 330.0, 35.0, 335.0, 80.0)
+    }
+
+    /// Creates a new LR Mate 200iD/7L robot, which has a 110mm longer upper arm and an 85mm
+    /// longer forearm than the 200iD. The dimensions are from the LR Mate 200iD/7L datasheet and
+    /// have not been checked against a controller.
+    pub fn new_200id_7l() -> Self {
+        Self::new(330.0, 50.0, 440.0, 35.0, 420.0, 80.0)
+    }
+
+    /// Compute the forward kinematics of a series of joint angles for the LR Mate series of
+    /// robots. The joints should be provided in degrees as they would appear in the robot
+    /// controller. The output will be a `Frame3` object representing the position and
+    /// orientation of the robot's tool frame (or the flange, if no tool is set) in relation to
+    /// the world, which is the robot origin unless a base is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `joints`: The joint angles for the robot in degrees. This should be an array of 6 values
+    ///   representing the angles for each joint in the order of J1, J2, J3, J4, J5, and J6.
+    ///
+    /// returns: Isometry<f64, Unit<Quaternion<f64>>, 3>
+    pub fn fk(&self, joints: &[f64; 6]) -> Frame3 {
+        self.fk_all(joints)[5] * self.tool
+    }
+
+    /// Compute the forward kinematics of a series of joint angles for the LR Mate series of
+    /// robots, returning the full kinematic chain for each joint in the robot. The final frame
+    /// in the array will represent the position and orientation of the robot's flange, and will
+    /// be identical to the result of the `fk` method when no tool is set. The other frames in the
+    /// array will be at the kinematic link origins, and do not have any corresponding values in
+    /// the actual robot.
+    ///
+    /// # Arguments
+    ///
+    /// * `joints`: The joint angles for the robot in degrees. This should be an array of 6 values
+    ///   representing the angles for each joint in the order of J1, J2, J3, J4, J5, and J6.
+    ///
+    /// returns: [Isometry<f64, Unit<Quaternion<f64>>, 3>; 6]
+    pub fn fk_all(&self, joints: &[f64; 6]) -> [Frame3; 6] {
+        let joints = joints_to_rad(joints);
+
+        // The first link is at the robot origin, rotated by the first joint angle
+        let f1 = self.base * Frame3::rotation(self.h[0] * joints[0]);
+
+        // J1->J2 shifts forward by the x0 value
+        let f2 = f1
+            * Frame3::from_parts(
+                Translation::<f64, 3>::new(self.x0, 0.0, 0.0),
+                UnitQuaternion::new(self.h[1] * joints[1]),
+            );
+
+        // J2->J3 shifts up by the z1 value
+        let f3 = f2
+            * Frame3::from_parts(
+                Translation::<f64, 3>::new(0.0, 0.0, self.z1),
+                UnitQuaternion::new(self.h[2] * joints[2]),
+            );
+
+        // J3->J4 shifts up by the z2 value
+        let f4 = f3
+            * Frame3::from_parts(
+                Translation::<f64, 3>::new(0.0, 0.0, self.z2),
+                UnitQuaternion::new(self.h[3] * joints[3]),
+            );
+
+        // J4->J5 shifts forward by x1 to the wrist center
+        let f5 = f4
+            * Frame3::from_parts(
+                Translation::<f64, 3>::new(self.x1, 0.0, 0.0),
+                UnitQuaternion::new(self.h[4] * joints[4]),
+            );
+
+        // J5->J6 shifts by x2, then gets re-oriented by the FANUC end effector adjustment
+        let f6 = f5
+            * Frame3::from_parts(
+                Translation::<f64, 3>::new(self.x2, 0.0, 0.0),
+                UnitQuaternion::new(self.h[5] * joints[5]),
+            )
+            * end_adjust();
+
+        [f1, f2, f3, f4, f5, f6]
+    }
+
+    /// Compute the 6x6 geometric Jacobian of the robot for a set of joint angles in degrees. The
+    /// Jacobian maps joint rates in radians per second, in the controller convention, to the
+    /// twist of the tool frame expressed in the world frame. The top three rows are the
+    /// linear velocity of the tool origin (mm/s) and the bottom three are its angular velocity
+    /// (rad/s).
+    ///
+    /// # Arguments
+    ///
+    /// * `joints`: The joint angles for the robot in degrees, as they would appear in the
+    ///   controller.
+    ///
+    /// returns: Matrix<f64, Const<6>, Const<6>, ArrayStorage<f64, 6, 6>>
+    pub fn jacobian(&self, joints: &[f64; 6]) -> Matrix6<f64> {
+        let chain = self.fk_all(joints);
+        jacobian(&chain, &self.h, &(chain[5] * self.tool))
+    }
+
+    /// Compute the inverse kinematics for a target tool frame, returning every set of joint angles
+    /// (in degrees, as they would appear in the controller) which places the tool at the target.
+    /// Because the wrist is spherical, there are up to eight solutions: two choices for J1
+    /// (front/back), two for the elbow (up/down), and two for the wrist (flip/no flip).
+    ///
+    /// Each solution is expanded into every equivalent set of joint angles (with joints 360
+    /// degrees apart) that fits within the joint limits, so J6 usually appears twice, and
+    /// solutions which can't be brought within the limits are discarded.
+    ///
+    /// # Arguments
+    ///
+    /// * `target`: the desired position and orientation of the tool frame (the flange if no tool
+    ///   is set) in relation to the world
+    ///
+    /// returns: Vec<[f64; 6], Global>
+    pub fn ik(&self, target: &Frame3) -> Vec<[f64; 6]> {
+        let target = &(self.base.inverse() * target * self.tool.inverse());
+        let mut results = Vec::new();
+        let wc = target * Point3::new(0.0, 0.0, -self.x2);
+
+        // The forearm is treated as a single link from the J3 axis to the wrist center, at an
+        // angle of `delta` above the forearm's own axis
+        let l = self.x1.hypot(self.z2);
+        let delta = self.z2.atan2(self.x1);
+
+        let j1 = wc.y.atan2(wc.x);
+        for q1 in [j1, j1 + PI] {
+            // The wrist center in the plane of the arm, relative to the J2 axis
+            let local = Frame3::rotation(self.h[0] * q1).inverse() * wc;
+            let px = local.x - self.x0;
+            let pz = local.z;
+
+            let s = (px.powi(2) + pz.powi(2) - self.z1.powi(2) - l.powi(2)) / (2.0 * self.z1 * l);
+            if s.abs() > 1.0 {
+                continue;
+            }
+
+            for gamma in [s.asin(), PI - s.asin()] {
+                let a = l * gamma.sin() + self.z1;
+                let b = l * gamma.cos();
+                let q2 = (a * px - b * pz).atan2(b * px + a * pz);
+
+                // psi is the angle of the forearm above horizontal, which is the J3 value in
+                // the controller convention
+                let psi = wrap(gamma - q2 - delta);
+                let q3 = psi + q2;
+
+                for wrist in self.wrist_solutions(q1, psi, target) {
+                    let joints =
+                        rad_to_joints(&[wrap(q1), q2, q3, wrist[0], wrist[1], wrist[2]]);
+                    results.extend(turn_variants(&joints, &self.limits));
+                }
+            }
+        }
+
+        results
+    }
+
+    /// Determine the FANUC configuration (flip, up, front, and the J1/J4/J6 turn numbers) of a set
+    /// of joint angles, as the controller would display it next to the Cartesian position.
+    ///
+    /// # Arguments
+    ///
+    /// * `joints`: the joint angles in degrees, in the controller convention
+    ///
+    /// returns: Config
+    pub fn config(&self, joints: &[f64; 6]) -> Config {
+        Config::from_chain(joints, &self.fk_all(joints))
+    }
+
+    /// Compute the inverse kinematics for a target tool frame and return the single solution
+    /// which matches a FANUC configuration, the same way the controller resolves a Cartesian
+    /// position recorded with a configuration string. If the turn numbers of the configuration
+    /// put any joint outside of its limits there is no solution.
+    ///
+    /// # Arguments
+    ///
+    /// * `target`: the desired position and orientation of the tool frame in relation to the
+    ///   world
+    /// * `config`: the configuration the solution must have
+    ///
+    /// returns: Option<[f64; 6]>
+    pub fn ik_config(&self, target: &Frame3, config: &Config) -> Option<[f64; 6]> {
+        self.ik(target)
+            .into_iter()
+            .find(|j| self.config(j) == *config)
+    }
+
+    /// Find the J4, J5, and J6 angles (in kinematic radians) which produce the target
+    /// orientation given J1 and the forearm angle `psi`.
+    fn wrist_solutions(&self, q1: f64, psi: f64, target: &Frame3) -> Vec<[f64; 3]> {
+        // The forearm orientation only depends on J1 and the forearm angle
+        let r3 = UnitQuaternion::new(self.h[0] * q1) * UnitQuaternion::new(-self.h[1] * psi);
+        let w = (r3.inverse() * target.rotation * end_adjust().rotation.inverse())
+            .to_rotation_matrix()
+            .into_inner();
+
+        // The remaining rotation is Rx(a) * Ry(b) * Rx(c), where a = -q4, b = -q5, and c = -q6
+        let sb = w[(1, 0)].hypot(w[(2, 0)]);
+        if sb < 1e-9 {
+            // The J4 and J6 axes are aligned, so only their combined rotation is defined. The
+            // convention is to leave J4 where it is and put the rotation on J6.
+            let (b, c) = if w[(0, 0)] > 0.0 {
+                (0.0, w[(2, 1)].atan2(w[(1, 1)]))
+            } else {
+                (PI, -w[(2, 1)].atan2(w[(1, 1)]))
+            };
+            return vec![[0.0, wrap(-b), wrap(-c)]];
+        }
+
+        let mut solutions = Vec::new();
+        for sign in [1.0, -1.0] {
+            let sb = sb * sign;
+            let a = (w[(1, 0)] / sb).atan2(-w[(2, 0)] / sb);
+            let b = sb.atan2(w[(0, 0)]);
+            let c = (w[(0, 1)] / sb).atan2(w[(0, 2)] / sb);
+            solutions.push([wrap(-a), wrap(-b), wrap(-c)]);
+        }
+
+        solutions
+    }
+}
+
+impl Robot for LrMate {
+    fn fk(&self, joints: &[f64; 6]) -> Frame3 {
+        LrMate::fk(self, joints)
+    }
+
+    fn fk_all(&self, joints: &[f64; 6]) -> [Frame3; 6] {
+        LrMate::fk_all(self, joints)
+    }
+
+    fn ik(&self, target: &Frame3) -> Vec<[f64; 6]> {
+        LrMate::ik(self, target)
+    }
+
+    fn jacobian(&self, joints: &[f64; 6]) -> Matrix6<f64> {
+        LrMate::jacobian(self, joints)
+    }
+
+    fn kinematic_angles(&self, joints: &[f64; 6]) -> [f64; 6] {
+        joints_to_rad(joints)
+    }
+
+    fn tool(&self) -> Frame3 {
+        self.tool
+    }
+
+    fn base(&self) -> Frame3 {
+        self.base
+    }
+
+    fn joint_limits(&self) -> [(f64, f64); 6] {
+        self.limits
+    }
+
+    fn z0(&self) -> f64 {
+        self.z0
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct LrMateDef {
+    z0: f64,
+    x0: f64,
+    z1: f64,
+    z2: f64,
+    x1: f64,
+    x2: f64,
+
+    #[serde(default)]
+    joint_limits: Option<[(f64, f64); 6]>,
+
+    #[serde(default)]
+    tool: Option<XyzWpr>,
+
+    #[serde(default)]
+    base: Option<XyzWpr>,
+}
+
+#[cfg(feature = "serde")]
+impl From<LrMate> for LrMateDef {
+    fn from(robot: LrMate) -> Self {
+        Self {
+            z0: robot.z0,
+            x0: robot.x0,
+            z1: robot.z1,
+            z2: robot.z2,
+            x1: robot.x1,
+            x2: robot.x2,
+            joint_limits: Some(robot.limits),
+            tool: Some(XyzWpr::from_isometry(&robot.tool)),
+            base: Some(XyzWpr::from_isometry(&robot.base)),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<LrMateDef> for LrMate {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(def: LrMateDef) -> Result<Self> {
+        let mut robot = LrMate::from_dimensions(def.z0, def.x0, def.z1, def.z2, def.x1, def.x2)?;
+        if let Some(limits) = def.joint_limits {
+            robot = robot.with_joint_limits(limits)?;
+        }
+        if let Some(tool) = def.tool {
+            robot.tool = tool.to_isometry();
+        }
+        if let Some(base) = def.base {
+            robot.base = base.to_isometry();
+        }
+        Ok(robot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Result;
+    use crate::helpers::row_slice_to_iso;
+    use crate::test_helpers::{assert_jacobian_matches_fk, same_joints};
+    use approx::assert_relative_eq;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn zero_position() -> Result<()> {
+        let j = [0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+        let expected = row_slice_to_iso(&[
+            0.0, 0.0, 1.0, 465.0, 0.0, -1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 365.0, 0.0, 0.0, 0.0, 1.0,
+        ])?;
+        let robot = LrMate::new_200id();
+        let fwd = robot.fk(&j);
+
+        assert_relative_eq!(expected, fwd, epsilon = 1e-6);
+        Ok(())
+    }
+
+    #[test]
+    fn ik_round_trip() -> Result<()> {
+        for robot in [LrMate::new_200id(), LrMate::new_200id_7l()] {
+            for joints in sampled_joints(&robot, 200) {
+                let expected = robot.fk(&joints);
+                let solutions = robot.ik(&expected);
+                assert!(!solutions.is_empty());
+
+                for solution in solutions.iter() {
+                    assert_relative_eq!(robot.fk(solution), expected, epsilon = 1e-6);
+                }
+
+                // Skip the wrist singularity, where J4 and J6 are not uniquely defined
+                if joints[4].abs() > 1e-3 {
+                    let found = solutions.iter().any(|s| same_joints(s, &joints));
+                    assert!(found, "{:?} not found in IK solutions", joints);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn config_home() {
+        let robot = LrMate::new_200id();
+        let config = robot.config(&[0.0, 0.0, 0.0, 0.0, -90.0, 0.0]);
+        assert_eq!(config.to_string(), "N U T, 0, 0, 0");
+    }
+
+    #[test]
+    fn ik_config_round_trip() -> Result<()> {
+        let robot = LrMate::new_200id();
+        let samples = sampled_joints(&robot, 200);
+        for joints in samples.iter().filter(|j| j[4].abs() > 1e-3) {
+            let expected = robot.fk(joints);
+            let config = robot.config(joints);
+            let solution = robot.ik_config(&expected, &config).unwrap();
+            assert!(same_joints(&solution, joints));
+            assert_eq!(robot.config(&solution), config);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn jacobian_matches_finite_difference() {
+        let joints = [10.0, -20.0, 30.0, -40.0, 50.0, -60.0];
+        assert_jacobian_matches_fk(&LrMate::new_200id(), &joints);
+    }
+
+    #[test]
+    fn ik_on_rail() {
+        // Mounted on its side on a rail carriage, with the mounting flange facing along +Y
+        let carriage = Frame3::from_parts(
+            Translation::<f64, 3>::new(1500.0, 0.0, 800.0),
+            UnitQuaternion::from_euler_angles(-PI / 2.0, 0.0, 0.0),
+        );
+        let robot = LrMate::new_200id().with_mount(carriage);
+        let joints = [10.0, -20.0, 30.0, -40.0, 50.0, -60.0];
+        let target = robot.fk(&joints);
+
+        assert_relative_eq!(
+            robot.base().translation.vector,
+            Vector3::new(1500.0, robot.z0(), 800.0),
+            epsilon = 1e-9
+        );
+        let solutions = robot.ik(&target);
+        assert!(solutions.iter().any(|s| same_joints(s, &joints)));
+        for s in solutions {
+            assert_relative_eq!(robot.fk(&s), target, epsilon = 1e-6);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() -> Result<()> {
+        let robot = LrMate::new_200id_7l().with_base(Frame3::translation(100.0, 200.0, 300.0));
+        let text = serde_json::to_string(&robot)?;
+        let loaded: LrMate = serde_json::from_str(&text)?;
+
+        let joints = [10.0, -20.0, 30.0, -40.0, 50.0, -60.0];
+        assert_relative_eq!(loaded.fk(&joints), robot.fk(&joints), epsilon = 1e-9);
+        assert_eq!(loaded.joint_limits(), robot.joint_limits());
+        Ok(())
+    }
+
+    #[test]
+    fn from_dimensions_matches_datasheet() -> Result<()> {
+        let robot = LrMate::from_dimensions(330.0, 50.0, 330.0, 35.0, 335.0, 80.0)?;
+        let j = [10.0, -20.0, 30.0, -40.0, 50.0, -60.0];
+        assert_relative_eq!(robot.fk(&j), LrMate::new_200id().fk(&j), epsilon = 1e-10);
+        Ok(())
+    }
+
+    #[test]
+    fn from_dimensions_rejects_degenerate() {
+        assert!(LrMate::from_dimensions(330.0, 50.0, 0.0, 35.0, 335.0, 80.0).is_err());
+        assert!(LrMate::from_dimensions(330.0, 50.0, 330.0, 35.0, 0.0, 80.0).is_err());
+        assert!(LrMate::from_dimensions(330.0, -50.0, 330.0, 35.0, 335.0, 80.0).is_err());
+        assert!(LrMate::from_dimensions(330.0, 50.0, 330.0, 35.0, 335.0, -1.0).is_err());
+        assert!(LrMate::from_dimensions(f64::NAN, 50.0, 330.0, 35.0, 335.0, 80.0).is_err());
+    }
+
+    #[test]
+    fn with_joint_limits_rejects_invalid() -> Result<()> {
+        let mut limits = LR_MATE_LIMITS;
+        limits[0] = (0.0, 20.0);
+        let robot = LrMate::new_200id().with_joint_limits(limits)?;
+        assert_eq!(robot.joint_limits(), limits);
+
+        limits[2] = (10.0, -10.0);
+        assert!(LrMate::new_200id().with_joint_limits(limits).is_err());
+
+        limits[2] = (f64::NAN, 10.0);
+        assert!(LrMate::new_200id().with_joint_limits(limits).is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_validation() {
+        let text = r#"{"z0": 330.0, "x0": 50.0, "z1": 0.0, "z2": 35.0, "x1": 335.0, "x2": 80.0}"#;
+        assert!(serde_json::from_str::<LrMate>(text).is_err());
+
+        let text = r#"{"z0": 330.0, "x0": 50.0, "z1": 330.0, "z2": 35.0, "x1": 335.0, "x2": 80.0,
+            "joint_limits": [[170.0, -170.0], [-100.0, 145.0], [-215.0, 215.0], [-190.0, 190.0],
+            [-125.0, 125.0], [-360.0, 360.0]]}"#;
+        assert!(serde_json::from_str::<LrMate>(text).is_err());
+    }
+
+    /// Random joint angles within the robot's limits. These only check that the kinematics are
+    /// consistent with themselves, since there is no controller data for the LR Mate yet.
+    fn sampled_joints(robot: &LrMate, count: usize) -> Vec<[f64; 6]> {
+        let mut rng = StdRng::seed_from_u64(200);
+        let limits = robot.joint_limits();
+        (0..count)
+            .map(|_| std::array::from_fn(|i| rng.random_range(limits[i].0..limits[i].1)))
+            .collect()
+    }
+}
@@ -0,0 +1,1121 @@
+//! Reading and writing FANUC ASCII teach pendant programs (`.LS` files). A program is parsed into
+//! its name and comment, the instructions of the `/MN` section, and the positions of the `/POS`
+//! section, which can then be inspected, simulated, or modified and written back out.
+//!
+//! Motion instructions, `UFRAME_NUM` and `UTOOL_NUM` assignments are parsed into typed values.
+//! Every other instruction is kept as its original text so that it survives a round trip. Only
+//! the first motion group (`GP1`) of each position is read, and the remaining `/ATTR` values are
+//! regenerated with their defaults when the program is written.
+//!
+//! ```
+//! use industrial_robots::fanuc::Crx;
+//! use industrial_robots::fanuc::ls::{LsProgram, MotionType, Position, Speed, Termination};
+//!
+//! let robot = Crx::new_5ia();
+//! let joints = [0.0, 10.0, -20.0, 0.0, -70.0, 0.0];
+//! let pose = robot.fk(&joints);
+//!
+//! let mut program = LsProgram::new("DEMO");
+//! program.add_move(MotionType::Joint, Position::joint(&joints), Speed::percent(50.0), Termination::Fine);
+//! program.add_move(
+//!     MotionType::Linear,
+//!     Position::cartesian(&pose, robot.config(&joints)),
+//!     Speed::mm_per_sec(250.0),
+//!     Termination::Cnt(50),
+//! );
+//!
+//! let text = program.to_string();
+//! let parsed: LsProgram = text.parse().unwrap();
+//! assert_eq!(parsed.positions.len(), 2);
+//! ```
+
+use crate::fanuc::Config;
+use crate::{Frame3, Result, XyzWpr};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// A FANUC teach pendant program
+#[derive(Debug, Clone, PartialEq)]
+pub struct LsProgram {
+    /// The program name, as it appears after `/PROG`
+    pub name: String,
+
+    /// The program comment from the `/ATTR` section
+    pub comment: String,
+
+    /// The instructions of the `/MN` section, where the instruction at index `i` is on line
+    /// `i + 1` of the program
+    pub instructions: Vec<Instruction>,
+
+    /// The positions of the `/POS` section
+    pub positions: Vec<Position>,
+}
+
+/// A single instruction (one numbered line) of a program
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    /// A motion instruction, such as `J P[1] 100% FINE`
+    Motion(Motion),
+
+    /// Selecting a user frame, `UFRAME_NUM=n`
+    UFrameNum(usize),
+
+    /// Selecting a tool frame, `UTOOL_NUM=n`
+    UToolNum(usize),
+
+    /// Any other instruction, kept as its text without the line number or the final `;`
+    Other(String),
+}
+
+/// The interpolation used by a motion instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotionType {
+    /// `J`, joint interpolated motion
+    Joint,
+
+    /// `L`, linear motion of the tool frame
+    Linear,
+
+    /// `C`, circular motion of the tool frame through a via point
+    Circular,
+}
+
+/// The position a motion instruction moves to, with the comment shown next to it in the `/MN`
+/// section (such as `P[1:Home]`), which is empty if there is none
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PositionRef {
+    /// A position stored in the program, `P[n]`
+    Local(usize, String),
+
+    /// A position register, `PR[n]`
+    Register(usize, String),
+}
+
+/// The units of a motion instruction's speed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpeedUnit {
+    /// `%`, a percentage of the maximum joint speed
+    Percent,
+
+    /// `mm/sec`
+    MmPerSec,
+
+    /// `cm/min`
+    CmPerMin,
+
+    /// `inch/min`
+    InchPerMin,
+
+    /// `deg/sec`
+    DegPerSec,
+
+    /// `sec`, the time the motion should take
+    Sec,
+
+    /// `msec`, the time the motion should take
+    Msec,
+}
+
+/// The speed of a motion instruction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Speed {
+    /// A fixed speed, such as `100%` or `500mm/sec`
+    Value(f64, SpeedUnit),
+
+    /// A speed read from a numeric register, such as `R[5]%`
+    Register(usize, SpeedUnit),
+
+    /// `max_speed`, the fastest the robot can follow a linear or circular path
+    Max,
+}
+
+/// How a motion instruction ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    /// `FINE`, stopping exactly at the position
+    Fine,
+
+    /// `CNTn`, blending into the next motion, from 0 (like `FINE`) to 100
+    Cnt(u32),
+}
+
+/// A motion instruction
+#[derive(Debug, Clone, PartialEq)]
+pub struct Motion {
+    pub kind: MotionType,
+
+    /// The position the motion ends at
+    pub target: PositionRef,
+
+    /// The via point of a circular motion, which is `None` for other motion types
+    pub via: Option<PositionRef>,
+
+    pub speed: Speed,
+    pub termination: Termination,
+
+    /// Any motion options after the termination type (such as `ACC80` or `Offset,PR[1]`), kept
+    /// as their text
+    pub options: Vec<String>,
+}
+
+/// A position from the `/POS` section
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    /// The position number, the `n` in `P[n]`
+    pub number: usize,
+
+    /// The position comment, which is empty if there is none
+    pub comment: String,
+
+    /// The user frame number the position was recorded in
+    pub uf: usize,
+
+    /// The tool frame number the position was recorded with
+    pub ut: usize,
+
+    pub data: PositionData,
+}
+
+/// The values of a position, which is either Cartesian or a set of joint angles
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PositionData {
+    /// A Cartesian position of the tool frame in the user frame, with the configuration which
+    /// selects the joint solution
+    Cartesian { pose: XyzWpr, config: Config },
+
+    /// Joint angles in degrees, in the controller convention
+    Joint([f64; 6]),
+}
+
+impl Speed {
+    pub fn new(value: f64, unit: SpeedUnit) -> Self {
+        Self::Value(value, unit)
+    }
+
+    /// A joint speed as a percentage of the maximum
+    pub fn percent(value: f64) -> Self {
+        Self::new(value, SpeedUnit::Percent)
+    }
+
+    /// A linear speed in mm/sec
+    pub fn mm_per_sec(value: f64) -> Self {
+        Self::new(value, SpeedUnit::MmPerSec)
+    }
+}
+
+impl PositionRef {
+    /// A position stored in the program, `P[n]`, without a comment
+    pub fn local(number: usize) -> Self {
+        Self::Local(number, String::new())
+    }
+
+    /// A position register, `PR[n]`, without a comment
+    pub fn register(number: usize) -> Self {
+        Self::Register(number, String::new())
+    }
+}
+
+impl Position {
+    /// Create a Cartesian position in user frame 0 with tool frame 1. The position number is
+    /// assigned when it is added to a program.
+    ///
+    /// # Arguments
+    ///
+    /// * `pose`: the pose of the tool frame, such as the result of `Crx::fk`
+    /// * `config`: the configuration of the arm, such as the result of `Crx::config`
+    ///
+    /// returns: Position
+    pub fn cartesian(pose: &Frame3, config: Config) -> Self {
+        Self {
+            number: 0,
+            comment: String::new(),
+            uf: 0,
+            ut: 1,
+            data: PositionData::Cartesian {
+                pose: XyzWpr::from_isometry(pose),
+                config,
+            },
+        }
+    }
+
+    /// Create a joint position with user frame 0 and tool frame 1. The position number is
+    /// assigned when it is added to a program.
+    ///
+    /// # Arguments
+    ///
+    /// * `joints`: the joint angles in degrees, such as a result of `Crx::ik`
+    ///
+    /// returns: Position
+    pub fn joint(joints: &[f64; 6]) -> Self {
+        Self {
+            number: 0,
+            comment: String::new(),
+            uf: 0,
+            ut: 1,
+            data: PositionData::Joint(*joints),
+        }
+    }
+
+    /// Return the position with different user and tool frame numbers
+    pub fn with_frames(mut self, uf: usize, ut: usize) -> Self {
+        self.uf = uf;
+        self.ut = ut;
+        self
+    }
+
+    /// Return the position with a comment
+    pub fn with_comment(mut self, comment: &str) -> Self {
+        self.comment = comment.to_string();
+        self
+    }
+}
+
+impl LsProgram {
+    /// Create an empty program
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            comment: String::new(),
+            instructions: Vec::new(),
+            positions: Vec::new(),
+        }
+    }
+
+    /// Find a position of the program by its number
+    pub fn position(&self, number: usize) -> Option<&Position> {
+        self.positions.iter().find(|p| p.number == number)
+    }
+
+    /// Add a position to the program, giving it the next unused position number, and return
+    /// that number
+    pub fn add_position(&mut self, mut position: Position) -> usize {
+        position.number = self.positions.iter().map(|p| p.number).max().unwrap_or(0) + 1;
+        let number = position.number;
+        self.positions.push(position);
+        number
+    }
+
+    /// Add a joint or linear motion to a new position at the end of the program, returning the
+    /// new position number. Use `add_circular` for circular motions.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind`: the motion type
+    /// * `position`: the position to move to, which is given the next unused number
+    /// * `speed`: the speed of the motion
+    /// * `termination`: how the motion ends
+    ///
+    /// returns: usize
+    pub fn add_move(
+        &mut self,
+        kind: MotionType,
+        position: Position,
+        speed: Speed,
+        termination: Termination,
+    ) -> usize {
+        let number = self.add_position(position);
+        self.instructions.push(Instruction::Motion(Motion {
+            kind,
+            target: PositionRef::local(number),
+            via: None,
+            speed,
+            termination,
+            options: Vec::new(),
+        }));
+        number
+    }
+
+    /// Add a circular motion through a via position to an end position at the end of the
+    /// program, returning the new via and end position numbers.
+    pub fn add_circular(
+        &mut self,
+        via: Position,
+        end: Position,
+        speed: Speed,
+        termination: Termination,
+    ) -> (usize, usize) {
+        let via = self.add_position(via);
+        let end = self.add_position(end);
+        self.instructions.push(Instruction::Motion(Motion {
+            kind: MotionType::Circular,
+            target: PositionRef::local(end),
+            via: Some(PositionRef::local(via)),
+            speed,
+            termination,
+            options: Vec::new(),
+        }));
+        (via, end)
+    }
+}
+
+// ================================================================================================
+// Parsing
+// ================================================================================================
+
+impl FromStr for LsProgram {
+    type Err = Box<dyn std::error::Error>;
+
+    /// Parse the text of an `.LS` file
+    fn from_str(s: &str) -> Result<Self> {
+        let mut program = LsProgram::new("");
+        let mut section = "";
+        let mut statement = String::new();
+        let mut position_text = String::new();
+
+        for (i, raw) in s.lines().enumerate() {
+            let line = raw.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with('/') && !line.starts_with("//") {
+                let mut parts = line.split_whitespace();
+                section = parts.next().unwrap_or("");
+                if section == "/PROG" {
+                    program.name = parts
+                        .next()
+                        .ok_or(format!("Missing program name on line {}", i + 1))?
+                        .to_string();
+                }
+                if section == "/END" {
+                    break;
+                }
+                continue;
+            }
+
+            match section {
+                "/ATTR" => {
+                    if let Some(value) = attribute(line, "COMMENT") {
+                        program.comment = value.trim_matches('"').to_string();
+                    }
+                }
+                "/MN" => {
+                    statement.push(' ');
+                    statement.push_str(line);
+                    if line.ends_with(';') {
+                        let instruction = parse_instruction(&statement)
+                            .map_err(|e| format!("{} (file line {})", e, i + 1))?;
+                        program.instructions.push(instruction);
+                        statement.clear();
+                    }
+                }
+                "/POS" => {
+                    position_text.push_str(line);
+                    position_text.push('\n');
+                    if line.starts_with("};") {
+                        program.positions.push(parse_position(&position_text)?);
+                        position_text.clear();
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if program.name.is_empty() {
+            return Err("Missing /PROG line".into());
+        }
+        if !statement.trim().is_empty() {
+            return Err(format!("Unterminated instruction '{}'", statement.trim()).into());
+        }
+        if !position_text.is_empty() {
+            return Err("Unterminated position in /POS section".into());
+        }
+
+        Ok(program)
+    }
+}
+
+/// Get the value of a `KEY = value;` attribute line
+fn attribute<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let (k, v) = line.split_once('=')?;
+    (k.trim() == key).then(|| v.trim().trim_end_matches(';').trim())
+}
+
+/// Parse a statement from the `/MN` section, which may span several lines
+fn parse_instruction(statement: &str) -> Result<Instruction> {
+    let text = statement.trim().trim_end_matches(';').trim();
+    let (number, body) = text
+        .split_once(':')
+        .ok_or(format!("Invalid instruction '{}'", text))?;
+    if number.trim().parse::<usize>().is_err() {
+        return Err(format!("Invalid line number in '{}'", text).into());
+    }
+
+    // Continuation lines of multi-line instructions start with a bare ':'
+    let body = body
+        .split(" :")
+        .map(|p| p.trim())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let body = body.trim();
+
+    let tokens = tokenize(body);
+    let kind = match tokens.first() {
+        Some(&"J") => Some(MotionType::Joint),
+        Some(&"L") => Some(MotionType::Linear),
+        Some(&"C") => Some(MotionType::Circular),
+        _ => None,
+    };
+
+    if let Some(kind) = kind {
+        return parse_motion(kind, &tokens[1..]).map(Instruction::Motion);
+    }
+
+    let compact = body.replace(' ', "");
+    if let Some(n) = compact.strip_prefix("UFRAME_NUM=")
+        && let Ok(n) = n.parse()
+    {
+        return Ok(Instruction::UFrameNum(n));
+    }
+    if let Some(n) = compact.strip_prefix("UTOOL_NUM=")
+        && let Ok(n) = n.parse()
+    {
+        return Ok(Instruction::UToolNum(n));
+    }
+
+    Ok(Instruction::Other(body.to_string()))
+}
+
+/// Split an instruction into whitespace separated tokens, keeping the text between square
+/// brackets together so that comments such as `P[1:Pick part]` stay in one token
+fn tokenize(body: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut depth = 0usize;
+    for (i, c) in body.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        if c.is_whitespace() && depth == 0 {
+            if let Some(s) = start.take() {
+                tokens.push(&body[s..i]);
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(&body[s..]);
+    }
+    tokens
+}
+
+fn parse_motion(kind: MotionType, tokens: &[&str]) -> Result<Motion> {
+    let mut tokens = tokens.iter();
+    let mut next = |what: &str| {
+        tokens
+            .next()
+            .copied()
+            .ok_or(format!("Missing {} in motion instruction", what))
+    };
+
+    let first = parse_position_ref(next("position")?)?;
+    let (target, via) = if kind == MotionType::Circular {
+        (parse_position_ref(next("end position")?)?, Some(first))
+    } else {
+        (first, None)
+    };
+
+    let speed = parse_speed(next("speed")?)?;
+    let termination = parse_termination(next("termination")?)?;
+    let options = tokens.map(|t| t.to_string()).collect();
+
+    Ok(Motion {
+        kind,
+        target,
+        via,
+        speed,
+        termination,
+        options,
+    })
+}
+
+fn parse_position_ref(token: &str) -> Result<PositionRef> {
+    let invalid = || format!("Invalid position reference '{}'", token);
+    let (prefix, rest) = token.split_once('[').ok_or_else(invalid)?;
+    let index = rest.strip_suffix(']').ok_or_else(invalid)?;
+
+    // Positions can carry a comment, such as P[1:Pick]
+    let (index, comment) = index.split_once(':').unwrap_or((index, ""));
+    let index = index.trim().parse().map_err(|_| invalid())?;
+    let comment = comment.to_string();
+
+    match prefix {
+        "P" => Ok(PositionRef::Local(index, comment)),
+        "PR" => Ok(PositionRef::Register(index, comment)),
+        _ => Err(invalid().into()),
+    }
+}
+
+fn parse_speed(token: &str) -> Result<Speed> {
+    if token == "max_speed" {
+        return Ok(Speed::Max);
+    }
+
+    // Longer suffixes first, so that "msec" isn't taken as "sec"
+    let units = [
+        ("mm/sec", SpeedUnit::MmPerSec),
+        ("cm/min", SpeedUnit::CmPerMin),
+        ("inch/min", SpeedUnit::InchPerMin),
+        ("deg/sec", SpeedUnit::DegPerSec),
+        ("msec", SpeedUnit::Msec),
+        ("sec", SpeedUnit::Sec),
+        ("%", SpeedUnit::Percent),
+    ];
+
+    for (suffix, unit) in units {
+        if let Some(value) = token.strip_suffix(suffix) {
+            let invalid = || format!("Invalid speed '{}'", token);
+
+            // The speed can be read from a numeric register, such as R[5]%
+            if let Some(register) = value.strip_prefix("R[") {
+                let register = register
+                    .strip_suffix(']')
+                    .and_then(|r| r.trim().parse().ok())
+                    .ok_or_else(invalid)?;
+                return Ok(Speed::Register(register, unit));
+            }
+
+            let value = value.parse().map_err(|_| invalid())?;
+            return Ok(Speed::new(value, unit));
+        }
+    }
+
+    Err(format!("Unsupported speed '{}'", token).into())
+}
+
+fn parse_termination(token: &str) -> Result<Termination> {
+    if token == "FINE" {
+        return Ok(Termination::Fine);
+    }
+
+    token
+        .strip_prefix("CNT")
+        .and_then(|v| v.parse().ok())
+        .map(Termination::Cnt)
+        .ok_or(format!("Unsupported termination type '{}'", token).into())
+}
+
+/// Parse the text of a single position, from `P[n]{` to `};`
+fn parse_position(text: &str) -> Result<Position> {
+    let (head, body) = text
+        .split_once('{')
+        .ok_or(format!("Invalid position '{}'", text.trim()))?;
+    let head = head.trim();
+    let inner = head
+        .strip_prefix("P[")
+        .and_then(|h| h.strip_suffix(']'))
+        .ok_or(format!("Invalid position header '{}'", head))?;
+
+    let (number, comment) = match inner.split_once(':') {
+        Some((n, c)) => (n, c.trim().trim_matches('"')),
+        None => (inner, ""),
+    };
+    let number = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid position number in '{}'", head))?;
+
+    // Only the first motion group is used, which runs until the next group or the end
+    let group = body
+        .split_once("GP1:")
+        .ok_or(format!("Position {} has no GP1 group", number))?
+        .1;
+    let group = group.split("GP2:").next().unwrap_or("");
+    let group = group.split("};").next().unwrap_or("");
+
+    let error = |what: &str| format!("Position {}: {}", number, what);
+
+    // The configuration string contains commas, so it's removed before splitting the rest
+    let mut config = None;
+    let mut rest = group.to_string();
+    if let Some(start) = group.find("CONFIG") {
+        let quoted = &group[start..];
+        let open = quoted.find('\'').ok_or_else(|| error("invalid CONFIG"))?;
+        let close = quoted[open + 1..]
+            .find('\'')
+            .ok_or_else(|| error("invalid CONFIG"))?;
+        let value = &quoted[open + 1..open + 1 + close];
+        config = Some(value.parse::<Config>()?);
+        rest = format!("{}{}", &group[..start], &quoted[open + close + 2..]);
+    }
+
+    let mut values = std::collections::HashMap::new();
+    for item in rest.split(',') {
+        let item = item.trim();
+        if item.is_empty() {
+            continue;
+        }
+
+        let (key, value) = item
+            .split_once('=')
+            .or_else(|| item.split_once(':'))
+            .ok_or_else(|| error(&format!("invalid value '{}'", item)))?;
+        let value = value
+            .split_whitespace()
+            .next()
+            .ok_or_else(|| error(&format!("missing value for '{}'", key.trim())))?;
+        let value = value
+            .parse::<f64>()
+            .map_err(|_| error(&format!("invalid number '{}'", value)))?;
+        values.insert(key.trim().to_string(), value);
+    }
+
+    let get = |key: &str| {
+        values
+            .get(key)
+            .copied()
+            .ok_or_else(|| error(&format!("missing {}", key)))
+    };
+
+    let data = if values.contains_key("J1") {
+        let mut joints = [0.0; 6];
+        for (i, j) in joints.iter_mut().enumerate() {
+            *j = get(&format!("J{}", i + 1))?;
+        }
+        PositionData::Joint(joints)
+    } else {
+        let pose = XyzWpr::new(
+            get("X")?,
+            get("Y")?,
+            get("Z")?,
+            get("W")?,
+            get("P")?,
+            get("R")?,
+        );
+        let config = config.ok_or_else(|| error("missing CONFIG"))?;
+        PositionData::Cartesian { pose, config }
+    };
+
+    Ok(Position {
+        number,
+        comment: comment.to_string(),
+        uf: get("UF")? as usize,
+        ut: get("UT")? as usize,
+        data,
+    })
+}
+
+// ================================================================================================
+// Writing
+// ================================================================================================
+
+impl Display for LsProgram {
+    /// Write the program as the text of an `.LS` file
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "/PROG  {}", self.name)?;
+        writeln!(f, "/ATTR")?;
+        writeln!(f, "OWNER\t\t= MNEDITOR;")?;
+        writeln!(f, "COMMENT\t\t= \"{}\";", self.comment)?;
+        writeln!(f, "PROG_SIZE\t= 0;")?;
+        writeln!(f, "CREATE\t\t= DATE 00-01-01  TIME 00:00:00;")?;
+        writeln!(f, "MODIFIED\t= DATE 00-01-01  TIME 00:00:00;")?;
+        writeln!(f, "FILE_NAME\t= ;")?;
+        writeln!(f, "VERSION\t\t= 0;")?;
+        writeln!(f, "LINE_COUNT\t= {};", self.instructions.len())?;
+        writeln!(f, "MEMORY_SIZE\t= 0;")?;
+        writeln!(f, "PROTECT\t\t= READ_WRITE;")?;
+        writeln!(f, "TCD:  STACK_SIZE\t= 0,")?;
+        writeln!(f, "      TASK_PRIORITY\t= 50,")?;
+        writeln!(f, "      TIME_SLICE\t= 0,")?;
+        writeln!(f, "      BUSY_LAMP_OFF\t= 0,")?;
+        writeln!(f, "      ABORT_REQUEST\t= 0,")?;
+        writeln!(f, "      PAUSE_REQUEST\t= 0;")?;
+        writeln!(f, "DEFAULT_GROUP\t= 1,*,*,*,*;")?;
+        writeln!(f, "CONTROL_CODE\t= 00000000 00000000;")?;
+
+        writeln!(f, "/MN")?;
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            write!(f, "{:>4}:", i + 1)?;
+            match instruction {
+                Instruction::Motion(m) if m.kind == MotionType::Circular => {
+                    // The end point goes on a continuation line
+                    let via = m.via.as_ref().unwrap_or(&m.target);
+                    writeln!(f, "C {}    ", via)?;
+                    writeln!(f, "    :  {} {}    ;", m.target, motion_tail(m))?;
+                }
+                other => writeln!(f, "{}    ;", other)?,
+            }
+        }
+
+        writeln!(f, "/POS")?;
+        for position in &self.positions {
+            write!(f, "{}", position)?;
+        }
+        writeln!(f, "/END")
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instruction::Motion(m) => {
+                let kind = match m.kind {
+                    MotionType::Joint => "J",
+                    MotionType::Linear => "L",
+                    MotionType::Circular => "C",
+                };
+                match &m.via {
+                    Some(via) => write!(f, "{} {} : {} {}", kind, via, m.target, motion_tail(m)),
+                    None => write!(f, "{} {} {}", kind, m.target, motion_tail(m)),
+                }
+            }
+            Instruction::UFrameNum(n) => write!(f, "  UFRAME_NUM={}", n),
+            Instruction::UToolNum(n) => write!(f, "  UTOOL_NUM={}", n),
+            Instruction::Other(text) => write!(f, "  {}", text),
+        }
+    }
+}
+
+/// The speed, termination, and options of a motion instruction
+fn motion_tail(m: &Motion) -> String {
+    let mut parts = vec![m.speed.to_string(), m.termination.to_string()];
+    parts.extend(m.options.iter().cloned());
+    parts.join(" ")
+}
+
+impl Display for PositionRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (prefix, n, comment) = match self {
+            PositionRef::Local(n, c) => ("P", n, c),
+            PositionRef::Register(n, c) => ("PR", n, c),
+        };
+        if comment.is_empty() {
+            write!(f, "{}[{}]", prefix, n)
+        } else {
+            write!(f, "{}[{}:{}]", prefix, n, comment)
+        }
+    }
+}
+
+impl Display for Speed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Speed::Value(value, unit) => write!(f, "{}{}", value, unit),
+            Speed::Register(n, unit) => write!(f, "R[{}]{}", n, unit),
+            Speed::Max => write!(f, "max_speed"),
+        }
+    }
+}
+
+impl Display for SpeedUnit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let unit = match self {
+            SpeedUnit::Percent => "%",
+            SpeedUnit::MmPerSec => "mm/sec",
+            SpeedUnit::CmPerMin => "cm/min",
+            SpeedUnit::InchPerMin => "inch/min",
+            SpeedUnit::DegPerSec => "deg/sec",
+            SpeedUnit::Sec => "sec",
+            SpeedUnit::Msec => "msec",
+        };
+        write!(f, "{}", unit)
+    }
+}
+
+impl Display for Termination {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Termination::Fine => write!(f, "FINE"),
+            Termination::Cnt(n) => write!(f, "CNT{}", n),
+        }
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.comment.is_empty() {
+            writeln!(f, "P[{}]{{", self.number)?;
+        } else {
+            writeln!(f, "P[{}:\"{}\"]{{", self.number, self.comment)?;
+        }
+        writeln!(f, "   GP1:")?;
+
+        match &self.data {
+            PositionData::Cartesian { pose, config } => {
+                writeln!(
+                    f,
+                    "\tUF : {}, UT : {},\t\tCONFIG : '{}',",
+                    self.uf, self.ut, config
+                )?;
+                writeln!(
+                    f,
+                    "\tX = {:>9.3}  mm,\tY = {:>9.3}  mm,\tZ = {:>9.3}  mm,",
+                    pose.x, pose.y, pose.z
+                )?;
+                writeln!(
+                    f,
+                    "\tW = {:>9.3} deg,\tP = {:>9.3} deg,\tR = {:>9.3} deg",
+                    pose.w, pose.p, pose.r
+                )?;
+            }
+            PositionData::Joint(j) => {
+                writeln!(f, "\tUF : {}, UT : {},", self.uf, self.ut)?;
+                writeln!(
+                    f,
+                    "\tJ1= {:>9.3} deg,\tJ2= {:>9.3} deg,\tJ3= {:>9.3} deg,",
+                    j[0], j[1], j[2]
+                )?;
+                writeln!(
+                    f,
+                    "\tJ4= {:>9.3} deg,\tJ5= {:>9.3} deg,\tJ6= {:>9.3} deg",
+                    j[3], j[4], j[5]
+                )?;
+            }
+        }
+
+        writeln!(f, "}};")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fanuc::Crx;
+    use approx::assert_relative_eq;
+
+    const SAMPLE: &str = r#"/PROG  PICK_PLACE	  Macro
+/ATTR
+OWNER		= MNEDITOR;
+COMMENT		= "Pick and place";
+PROG_SIZE	= 812;
+CREATE		= DATE 25-03-04  TIME 10:12:40;
+MODIFIED	= DATE 25-03-04  TIME 10:20:02;
+FILE_NAME	= ;
+VERSION		= 0;
+LINE_COUNT	= 7;
+MEMORY_SIZE	= 1196;
+PROTECT		= READ_WRITE;
+TCD:  STACK_SIZE	= 0,
+      TASK_PRIORITY	= 50,
+      TIME_SLICE	= 0,
+      BUSY_LAMP_OFF	= 0,
+      ABORT_REQUEST	= 0,
+      PAUSE_REQUEST	= 0;
+DEFAULT_GROUP	= 1,*,*,*,*;
+CONTROL_CODE	= 00000000 00000000;
+/APPL
+/MN
+   1:  UFRAME_NUM=1 ;
+   2:  UTOOL_NUM=2 ;
+   3:J P[1:Home] 100% FINE    ;
+   4:L P[2] 500mm/sec CNT50 ACC80    ;
+   5:C P[3]
+    :  P[4] 250mm/sec FINE    ;
+   6:  WAIT   1.00(sec) ;
+   7:L PR[5] 2000mm/sec CNT100    ;
+/POS
+P[1:"Home"]{
+   GP1:
+	UF : 1, UT : 2,
+	J1=     0.000 deg,	J2=     0.000 deg,	J3=     0.000 deg,
+	J4=     0.000 deg,	J5=   -90.000 deg,	J6=     0.000 deg
+};
+P[2]{
+   GP1:
+	UF : 1, UT : 2,		CONFIG : 'N U T, 0, 0, 0',
+	X =   500.000  mm,	Y =  -100.000  mm,	Z =   300.000  mm,
+	W =   180.000 deg,	P =     0.000 deg,	R =   -90.000 deg
+};
+P[3]{
+   GP1:
+	UF : 1, UT : 2,		CONFIG : 'N U T, 0, 0, 0',
+	X =   550.000  mm,	Y =     0.000  mm,	Z =   300.000  mm,
+	W =   180.000 deg,	P =     0.000 deg,	R =   -90.000 deg
+};
+P[4]{
+   GP1:
+	UF : 1, UT : 2,		CONFIG : 'F U T, 0, 0, -1',
+	X =   500.000  mm,	Y =   100.000  mm,	Z =   300.000  mm,
+	W =   180.000 deg,	P =     0.000 deg,	R =   -90.000 deg
+};
+/END
+"#;
+
+    #[test]
+    fn parse_sample() -> Result<()> {
+        let program: LsProgram = SAMPLE.parse()?;
+        assert_eq!(program.name, "PICK_PLACE");
+        assert_eq!(program.comment, "Pick and place");
+        assert_eq!(program.instructions.len(), 7);
+        assert_eq!(program.instructions[0], Instruction::UFrameNum(1));
+        assert_eq!(program.instructions[1], Instruction::UToolNum(2));
+
+        let Instruction::Motion(m) = &program.instructions[3] else {
+            panic!("Expected a motion on line 4");
+        };
+        assert_eq!(m.kind, MotionType::Linear);
+        assert_eq!(m.target, PositionRef::local(2));
+        assert_eq!(m.speed, Speed::mm_per_sec(500.0));
+        assert_eq!(m.termination, Termination::Cnt(50));
+        assert_eq!(m.options, vec!["ACC80".to_string()]);
+
+        let Instruction::Motion(m) = &program.instructions[4] else {
+            panic!("Expected a motion on line 5");
+        };
+        assert_eq!(m.kind, MotionType::Circular);
+        assert_eq!(m.via, Some(PositionRef::local(3)));
+        assert_eq!(m.target, PositionRef::local(4));
+
+        assert_eq!(
+            program.instructions[5],
+            Instruction::Other("WAIT   1.00(sec)".to_string())
+        );
+        assert!(matches!(
+            &program.instructions[6],
+            Instruction::Motion(Motion {
+                target: PositionRef::Register(5, _),
+                ..
+            })
+        ));
+
+        assert_eq!(program.positions.len(), 4);
+        let home = program.position(1).unwrap();
+        assert_eq!(home.comment, "Home");
+        assert_eq!((home.uf, home.ut), (1, 2));
+        assert_eq!(
+            home.data,
+            PositionData::Joint([0.0, 0.0, 0.0, 0.0, -90.0, 0.0])
+        );
+
+        let PositionData::Cartesian { pose, config } = program.position(4).unwrap().data else {
+            panic!("Expected a Cartesian position");
+        };
+        assert_eq!(pose, XyzWpr::new(500.0, 100.0, 300.0, 180.0, 0.0, -90.0));
+        assert_eq!(config, Config::new(true, true, true, [0, 0, -1]));
+        Ok(())
+    }
+
+    #[test]
+    fn write_round_trip() -> Result<()> {
+        let program: LsProgram = SAMPLE.parse()?;
+        let text = program.to_string();
+        let reparsed: LsProgram = text.parse()?;
+
+        assert_eq!(reparsed, program);
+        assert!(text.contains("   5:C P[3]    \n    :  P[4] 250mm/sec FINE    ;\n"));
+        assert!(text.contains("   4:L P[2] 500mm/sec CNT50 ACC80    ;\n"));
+        assert!(text.contains("   3:J P[1:Home] 100% FINE    ;\n"));
+        assert!(text.contains("LINE_COUNT\t= 7;"));
+        Ok(())
+    }
+
+    #[test]
+    fn speed_forms_and_comments() -> Result<()> {
+        let text = "/PROG A\n/MN\n   1:L P[2] max_speed CNT100    ;\n   2:J P[1:Pick part] R[5]% \
+                    FINE    ;\n   3:L PR[3:Approach] R[12]mm/sec CNT20    ;\n/END\n";
+        let program: LsProgram = text.parse()?;
+
+        let motions = program
+            .instructions
+            .iter()
+            .map(|i| match i {
+                Instruction::Motion(m) => (m.target.clone(), m.speed),
+                _ => panic!("Expected a motion"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            motions,
+            vec![
+                (PositionRef::local(2), Speed::Max),
+                (
+                    PositionRef::Local(1, "Pick part".to_string()),
+                    Speed::Register(5, SpeedUnit::Percent)
+                ),
+                (
+                    PositionRef::Register(3, "Approach".to_string()),
+                    Speed::Register(12, SpeedUnit::MmPerSec)
+                ),
+            ]
+        );
+
+        let written = program.to_string();
+        assert!(written.contains("   1:L P[2] max_speed CNT100    ;\n"));
+        assert!(written.contains("   2:J P[1:Pick part] R[5]% FINE    ;\n"));
+        assert!(written.contains("   3:L PR[3:Approach] R[12]mm/sec CNT20    ;\n"));
+        assert_eq!(written.parse::<LsProgram>()?, program);
+        Ok(())
+    }
+
+    #[test]
+    fn program_from_kinematics() -> Result<()> {
+        let robot = Crx::new_5ia();
+        let path = [
+            [0.0, 10.0, -20.0, 0.0, -70.0, 0.0],
+            [20.0, 15.0, -10.0, 10.0, -60.0, 30.0],
+            [-30.0, 5.0, -25.0, -20.0, -80.0, -45.0],
+        ];
+
+        let mut program = LsProgram::new("FROM_FK");
+        program.add_move(
+            MotionType::Joint,
+            Position::joint(&path[0]).with_comment("start"),
+            Speed::percent(50.0),
+            Termination::Fine,
+        );
+        for joints in &path[1..] {
+            program.add_move(
+                MotionType::Linear,
+                Position::cartesian(&robot.fk(joints), robot.config(joints)),
+                Speed::mm_per_sec(250.0),
+                Termination::Cnt(100),
+            );
+        }
+
+        let parsed: LsProgram = program.to_string().parse()?;
+        assert_eq!(parsed.positions.len(), 3);
+        assert_eq!(parsed.position(1).unwrap().comment, "start");
+
+        // The written values are rounded to three decimals, so solving the parsed positions
+        // with their configuration lands within that rounding of the original joints
+        for (position, joints) in parsed.positions[1..].iter().zip(&path[1..]) {
+            let PositionData::Cartesian { pose, config } = position.data else {
+                panic!("Expected a Cartesian position");
+            };
+            let solution = robot.ik_config(&pose.to_isometry(), &config).unwrap();
+            for (a, b) in solution.iter().zip(joints.iter()) {
+                assert_relative_eq!(a, b, epsilon = 1e-2);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn circular_builder() -> Result<()> {
+        let mut program = LsProgram::new("ARC");
+        let (via, end) = program.add_circular(
+            Position::joint(&[0.0; 6]),
+            Position::joint(&[10.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+            Speed::mm_per_sec(100.0),
+            Termination::Fine,
+        );
+        assert_eq!((via, end), (1, 2));
+
+        let parsed: LsProgram = program.to_string().parse()?;
+        assert_eq!(parsed, program);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_errors() {
+        let missing_prog = "/MN\n   1:J P[1] 100% FINE ;\n/END\n";
+        assert!(missing_prog.parse::<LsProgram>().is_err());
+
+        let bad_speed = "/PROG A\n/MN\n   1:J P[1] fast FINE ;\n/END\n";
+        let err = bad_speed.parse::<LsProgram>().unwrap_err().to_string();
+        assert!(err.contains("fast"));
+
+        let bad_register = "/PROG A\n/MN\n   1:J P[1] R[x]% FINE ;\n/END\n";
+        assert!(bad_register.parse::<LsProgram>().is_err());
+
+        let bad_termination = "/PROG A\n/MN\n   1:L P[1] 100mm/sec CD50 ;\n/END\n";
+        assert!(bad_termination.parse::<LsProgram>().is_err());
+
+        let no_config = "/PROG A\n/POS\nP[1]{\n GP1:\n UF : 0, UT : 1,\n X = 1 mm, Y = 2 mm, \
+                         Z = 3 mm,\n W = 0 deg, P = 0 deg, R = 0 deg\n};\n/END\n";
+        let err = no_config.parse::<LsProgram>().unwrap_err().to_string();
+        assert!(err.contains("CONFIG"));
+    }
+}
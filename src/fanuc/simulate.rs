@@ -0,0 +1,841 @@
+//! Offline simulation of FANUC teach pendant programs on a CRX model. The motion instructions of
+//! a parsed `LsProgram` are replayed in order, turning each `J`, `L`, and `C` move into a series
+//! of joint angle samples. Every sample is checked for reachability, joint limits, and
+//! (optionally) collisions in a `CollisionScene`, and the simulation stops at the first line
+//! which would fail on the robot.
+//!
+//! Joint moves are interpolated in joint space, while linear and circular moves interpolate the
+//! tool frame in Cartesian space and solve the inverse kinematics at every sample, taking the
+//! solution closest to the previous sample. A sample which can only be reached by a large jump of
+//! some joint is reported as a configuration change, which is how passing through or near a
+//! singularity shows up.
+//!
+//! Positions recorded in user frame zero are relative to the robot origin, the same as on the
+//! controller. Other user frames and the tool frames come from `FrameTable`s given to the
+//! simulator, where user frames are relative to the world.
+
+use crate::collision::{CollisionScene, link_transforms};
+use crate::fanuc::ls::{
+    Instruction, LsProgram, Motion, MotionType, Position, PositionData, PositionRef,
+};
+use crate::fanuc::{Config, Crx, FrameTable};
+use crate::nalgebra::{Translation3, UnitQuaternion};
+use crate::{Frame3, Point3, Robot, Vector3};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// Options which control how finely motions are sampled
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimulationOptions {
+    /// The largest distance (mm) the tool may travel between samples of a linear or circular move
+    pub linear_step: f64,
+
+    /// The largest rotation (degrees) of the tool between samples of a linear or circular move
+    pub angular_step: f64,
+
+    /// The largest change (degrees) of any joint between samples of a joint move
+    pub joint_step: f64,
+
+    /// The largest change (degrees) of any joint between samples of a linear or circular move.
+    /// A larger change means the path passes through a singularity or would need the arm to
+    /// change configuration.
+    pub max_joint_jump: f64,
+}
+
+impl Default for SimulationOptions {
+    fn default() -> Self {
+        Self {
+            linear_step: 5.0,
+            angular_step: 2.0,
+            joint_step: 2.0,
+            max_joint_jump: 10.0,
+        }
+    }
+}
+
+/// Why a line of a program failed in simulation
+#[derive(Debug, Clone, PartialEq)]
+pub enum FailureReason {
+    /// The motion refers to a position which isn't in the program or to a position register
+    /// which hasn't been given to the simulator
+    MissingPosition(PositionRef),
+
+    /// The user frame number isn't in the simulator's user frame table
+    UndefinedUFrame(usize),
+
+    /// The tool frame number isn't in the simulator's tool frame table
+    UndefinedUTool(usize),
+
+    /// The position was recorded in a different user or tool frame than the active one, given
+    /// as `(uf, ut)` pairs
+    FrameMismatch {
+        position: (usize, usize),
+        active: (usize, usize),
+    },
+
+    /// There is no inverse kinematics solution for the target, or for a point along the path
+    Unreachable,
+
+    /// The joint angles are outside of the robot's joint limits
+    OutsideLimits([f64; 6]),
+
+    /// Following the path requires a jump in the joint angles, or it ends in a different arm
+    /// configuration than the target was recorded with
+    ConfigurationChange,
+
+    /// The three points of a circular move are collinear or coincide
+    InvalidArc,
+
+    /// The robot collides at the given joint angles, with the colliding pairs of mesh ids
+    Collision {
+        joints: [f64; 6],
+        pairs: Vec<(usize, usize)>,
+    },
+
+    /// The collision scene couldn't be checked, such as when a link id isn't in the scene
+    Scene(String),
+}
+
+/// The first line of a program which failed in simulation
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationFailure {
+    /// The line number in the `/MN` section, starting at one
+    pub line: usize,
+
+    pub reason: FailureReason,
+}
+
+/// A single sample of the simulated joint trajectory
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrajectoryPoint {
+    /// The line number of the motion which produced the sample
+    pub line: usize,
+
+    /// The joint angles in degrees
+    pub joints: [f64; 6],
+}
+
+/// The result of simulating a program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Simulation {
+    /// Every sample that passed its checks, in order, up to the first failure
+    pub trajectory: Vec<TrajectoryPoint>,
+
+    /// The first failing line, if there was one
+    pub failure: Option<SimulationFailure>,
+}
+
+impl Simulation {
+    /// Check if the whole program ran without failing
+    pub fn succeeded(&self) -> bool {
+        self.failure.is_none()
+    }
+
+    /// The first failing line, if there was one
+    pub fn failed_line(&self) -> Option<usize> {
+        self.failure.as_ref().map(|f| f.line)
+    }
+
+    /// The joint angles at the end of the simulation
+    pub fn final_joints(&self) -> Option<[f64; 6]> {
+        self.trajectory.last().map(|p| p.joints)
+    }
+}
+
+/// Replays the motion instructions of FANUC programs on a CRX model
+pub struct Simulator<'a> {
+    robot: &'a Crx,
+    utools: FrameTable,
+    uframes: FrameTable,
+    registers: HashMap<usize, Position>,
+    scene: Option<(&'a CollisionScene, [usize; 7])>,
+    options: SimulationOptions,
+}
+
+impl<'a> Simulator<'a> {
+    /// Create a simulator for a robot, with empty frame tables, no position registers, and no
+    /// collision checking. The robot's own tool is replaced by the tool frames of the program.
+    pub fn new(robot: &'a Crx) -> Self {
+        Self {
+            robot,
+            utools: FrameTable::utool(),
+            uframes: FrameTable::uframe(),
+            registers: HashMap::new(),
+            scene: None,
+            options: SimulationOptions::default(),
+        }
+    }
+
+    /// Return the simulator with a tool frame table, used to look up the `UT` of positions
+    pub fn with_utools(mut self, utools: FrameTable) -> Self {
+        self.utools = utools;
+        self
+    }
+
+    /// Return the simulator with a user frame table, used to look up the `UF` of positions
+    pub fn with_uframes(mut self, uframes: FrameTable) -> Self {
+        self.uframes = uframes;
+        self
+    }
+
+    /// Return the simulator with the value of a position register, used by `PR[n]` motions. As
+    /// on the controller, a register is interpreted in the active user and tool frames, so its
+    /// `uf` and `ut` are only used before a frame has been selected.
+    pub fn with_register(mut self, number: usize, position: Position) -> Self {
+        self.registers.insert(number, position);
+        self
+    }
+
+    /// Return the simulator with different sampling options
+    pub fn with_options(mut self, options: SimulationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Return the simulator with collision checking against a scene.
+    ///
+    /// # Arguments
+    ///
+    /// * `scene`: the scene holding the robot's link meshes as interest meshes, along with any
+    ///   obstacles
+    /// * `links`: the ids of the seven link meshes in the scene, in the order of
+    ///   `PosedRobot::links`, which is the simplest way to register them. An id which isn't in the
+    ///   scene fails the first sample that's checked with `FailureReason::Scene`.
+    ///
+    /// returns: Simulator
+    pub fn with_scene(mut self, scene: &'a CollisionScene, links: [usize; 7]) -> Self {
+        self.scene = Some((scene, links));
+        self
+    }
+
+    /// Simulate a program starting from a set of joint angles.
+    ///
+    /// The active user and tool frames start out unset, and are set by `UFRAME_NUM` and
+    /// `UTOOL_NUM` instructions. Once one is set, moving to a position recorded in a different
+    /// frame fails, the same as on the controller, while position registers are taken to be in
+    /// the active frames. Instructions other than motions and frame selections are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `program`: the program to simulate
+    /// * `start`: the joint angles in degrees the robot starts at
+    ///
+    /// returns: Simulation
+    pub fn run(&self, program: &LsProgram, start: &[f64; 6]) -> Simulation {
+        let mut state = State {
+            joints: *start,
+            uf: None,
+            ut: None,
+        };
+        let mut trajectory = Vec::new();
+
+        for (i, instruction) in program.instructions.iter().enumerate() {
+            let line = i + 1;
+            let result = match instruction {
+                Instruction::UFrameNum(n) => self
+                    .uframe(*n)
+                    .map(|_| state.uf = Some(*n))
+                    .map(|_| Vec::new()),
+                Instruction::UToolNum(n) => self
+                    .utools
+                    .get(*n)
+                    .ok_or(FailureReason::UndefinedUTool(*n))
+                    .map(|_| state.ut = Some(*n))
+                    .map(|_| Vec::new()),
+                Instruction::Motion(m) => self.motion(program, m, &state),
+                Instruction::Other(_) => Ok(Vec::new()),
+            };
+
+            let failure = result.and_then(|samples| {
+                for joints in samples {
+                    self.check(&joints)?;
+                    trajectory.push(TrajectoryPoint { line, joints });
+                    state.joints = joints;
+                }
+                Ok(())
+            });
+
+            if let Err(reason) = failure {
+                return Simulation {
+                    trajectory,
+                    failure: Some(SimulationFailure { line, reason }),
+                };
+            }
+        }
+
+        Simulation {
+            trajectory,
+            failure: None,
+        }
+    }
+
+    /// Generate the joint samples of a motion, not including the starting point
+    fn motion(&self, program: &LsProgram, motion: &Motion, state: &State) -> Sampled {
+        let target = self.resolve(program, &motion.target, state)?;
+        let robot = self.robot.clone().with_tool(target.tool);
+
+        match motion.kind {
+            MotionType::Joint => {
+                let end = match target.data {
+                    PositionData::Cartesian { pose, config } => robot
+                        .ik_config(&(target.uframe * pose.to_isometry()), &config)
+                        .ok_or(FailureReason::Unreachable)?,
+                    PositionData::Joint(joints) => joints,
+                };
+                Ok(self.joint_samples(&state.joints, &end))
+            }
+            MotionType::Linear => {
+                let start = robot.fk(&state.joints);
+                let (end, config) = target.cartesian(&robot);
+                let path = |s: f64| interpolate(&start, &end, s);
+                let length = (end.translation.vector - start.translation.vector).norm();
+                let angle = start.rotation.angle_to(&end.rotation).to_degrees();
+                self.path_samples(&robot, &state.joints, path, length, angle, &config)
+            }
+            MotionType::Circular => {
+                let via_ref = motion.via.as_ref().ok_or(FailureReason::InvalidArc)?;
+                let via = self.resolve(program, via_ref, state)?;
+                let start = robot.fk(&state.joints);
+                let (mid, _) = via.cartesian(&robot);
+                let (end, config) = target.cartesian(&robot);
+
+                let arc = Arc::new(
+                    &start.translation.vector.into(),
+                    &mid.translation.vector.into(),
+                    &end.translation.vector.into(),
+                )
+                .ok_or(FailureReason::InvalidArc)?;
+
+                // The orientation turns from the start to the via point over the first part of
+                // the arc, and from the via point to the end over the rest
+                let split = arc.via_angle / arc.angle;
+                let path = |s: f64| {
+                    let rotation = if s <= split {
+                        slerp(&start.rotation, &mid.rotation, s / split)
+                    } else {
+                        slerp(&mid.rotation, &end.rotation, (s - split) / (1.0 - split))
+                    };
+                    Frame3::from_parts(Translation3::from(arc.point(s).coords), rotation)
+                };
+                let angle = (start.rotation.angle_to(&mid.rotation)
+                    + mid.rotation.angle_to(&end.rotation))
+                .to_degrees();
+                self.path_samples(&robot, &state.joints, path, arc.length(), angle, &config)
+            }
+        }
+    }
+
+    /// Sample a joint move by interpolating each joint
+    fn joint_samples(&self, start: &[f64; 6], end: &[f64; 6]) -> Vec<[f64; 6]> {
+        let largest = start
+            .iter()
+            .zip(end.iter())
+            .map(|(a, b)| (b - a).abs())
+            .fold(0.0, f64::max);
+        let count = ((largest / self.options.joint_step).ceil() as usize).max(1);
+
+        (1..=count)
+            .map(|k| {
+                let s = k as f64 / count as f64;
+                let mut joints = *start;
+                for (j, e) in joints.iter_mut().zip(end.iter()) {
+                    *j += (e - *j) * s;
+                }
+                joints
+            })
+            .collect()
+    }
+
+    /// Sample a Cartesian path by solving the inverse kinematics at each sample, following the
+    /// solution closest to the previous one
+    fn path_samples(
+        &self,
+        robot: &Crx,
+        start: &[f64; 6],
+        path: impl Fn(f64) -> Frame3,
+        length: f64,
+        angle: f64,
+        config: &Config,
+    ) -> Sampled {
+        let count = (length / self.options.linear_step)
+            .max(angle / self.options.angular_step)
+            .ceil()
+            .max(1.0) as usize;
+
+        let mut samples = Vec::with_capacity(count);
+        let mut previous = *start;
+        for k in 1..=count {
+            let pose = path(k as f64 / count as f64);
+            let joints = *robot
+                .ik_closest(&pose, &previous, None)
+                .first()
+                .ok_or(FailureReason::Unreachable)?;
+
+            let jump = joints
+                .iter()
+                .zip(previous.iter())
+                .any(|(a, b)| (a - b).abs() > self.options.max_joint_jump);
+            if jump {
+                return Err(FailureReason::ConfigurationChange);
+            }
+
+            samples.push(joints);
+            previous = joints;
+        }
+
+        if !robot.config(&previous).same_arm(config) {
+            return Err(FailureReason::ConfigurationChange);
+        }
+
+        Ok(samples)
+    }
+
+    /// Check a single sample for joint limits and collisions
+    fn check(&self, joints: &[f64; 6]) -> Result<(), FailureReason> {
+        if !self.robot.within_limits(joints) {
+            return Err(FailureReason::OutsideLimits(*joints));
+        }
+
+        if let Some((scene, links)) = &self.scene {
+            let transforms = link_transforms(self.robot, links, joints);
+            let pairs = scene
+                .check_all(&transforms, true, None)
+                .map_err(|e| FailureReason::Scene(e.to_string()))?;
+            if !pairs.is_empty() {
+                return Err(FailureReason::Collision {
+                    joints: *joints,
+                    pairs,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Look up a position and the frames it was recorded in
+    fn resolve(
+        &self,
+        program: &LsProgram,
+        reference: &PositionRef,
+        state: &State,
+    ) -> Result<Target, FailureReason> {
+        let position = match reference {
+            PositionRef::Local(n, _) => program.position(*n),
+            PositionRef::Register(n, _) => self.registers.get(n),
+        }
+        .ok_or_else(|| FailureReason::MissingPosition(reference.clone()))?;
+
+        let active = (
+            state.uf.unwrap_or(position.uf),
+            state.ut.unwrap_or(position.ut),
+        );
+
+        // Position registers don't carry their frames on the controller, and are always
+        // interpreted in the active ones
+        let is_register = matches!(reference, PositionRef::Register(..));
+        if !is_register && active != (position.uf, position.ut) {
+            return Err(FailureReason::FrameMismatch {
+                position: (position.uf, position.ut),
+                active,
+            });
+        }
+
+        let (uf, ut) = active;
+        Ok(Target {
+            uframe: self.uframe(uf)?,
+            tool: self
+                .utools
+                .get(ut)
+                .ok_or(FailureReason::UndefinedUTool(ut))?,
+            data: position.data,
+        })
+    }
+
+    /// Look up a user frame in the world, where user frame zero is the robot origin
+    fn uframe(&self, number: usize) -> Result<Frame3, FailureReason> {
+        match number {
+            0 => Ok(self.robot.base()),
+            n => self.uframes.get(n).ok_or(FailureReason::UndefinedUFrame(n)),
+        }
+    }
+}
+
+type Sampled = Result<Vec<[f64; 6]>, FailureReason>;
+
+struct State {
+    joints: [f64; 6],
+    uf: Option<usize>,
+    ut: Option<usize>,
+}
+
+/// A resolved position, with its user frame in the world and its tool frame
+struct Target {
+    uframe: Frame3,
+    tool: Frame3,
+    data: PositionData,
+}
+
+impl Target {
+    /// The pose of the tool frame in the world along with the configuration of the arm, given a
+    /// robot which already has the target's tool set
+    fn cartesian(&self, robot: &Crx) -> (Frame3, Config) {
+        match self.data {
+            PositionData::Cartesian { pose, config } => (self.uframe * pose.to_isometry(), config),
+            PositionData::Joint(joints) => (robot.fk(&joints), robot.config(&joints)),
+        }
+    }
+}
+
+/// A circular arc through three points
+struct Arc {
+    center: Point3,
+    radius: f64,
+    u: Vector3,
+    v: Vector3,
+
+    /// The angle (radians) from the start to the via point
+    via_angle: f64,
+
+    /// The angle (radians) from the start to the end point
+    angle: f64,
+}
+
+impl Arc {
+    fn new(start: &Point3, via: &Point3, end: &Point3) -> Option<Self> {
+        let a = start - end;
+        let b = via - end;
+        let normal = a.cross(&b);
+        if normal.norm() < 1e-6 * a.norm().max(b.norm()).max(1.0) {
+            return None;
+        }
+
+        let offset = (b * a.norm_squared() - a * b.norm_squared()).cross(&normal)
+            / (2.0 * normal.norm_squared());
+        let center = end + offset;
+        let radius = (start - center).norm();
+
+        // Going around the normal from the start passes the via point before the end point
+        let u = (start - center).normalize();
+        let v = (via - start).cross(&(end - start)).normalize().cross(&u);
+        let angle_of = |p: &Point3| {
+            let d = p - center;
+            d.dot(&v).atan2(d.dot(&u)).rem_euclid(std::f64::consts::TAU)
+        };
+
+        Some(Self {
+            center,
+            radius,
+            u,
+            v,
+            via_angle: angle_of(via),
+            angle: angle_of(end),
+        })
+    }
+
+    fn length(&self) -> f64 {
+        self.radius * self.angle
+    }
+
+    /// The point at a fraction of the way along the arc
+    fn point(&self, s: f64) -> Point3 {
+        let theta = self.angle * s;
+        self.center + (self.u * theta.cos() + self.v * theta.sin()) * self.radius
+    }
+}
+
+/// Interpolate a frame, moving the origin in a straight line and rotating about a fixed axis
+fn interpolate(start: &Frame3, end: &Frame3, s: f64) -> Frame3 {
+    let translation = start.translation.vector.lerp(&end.translation.vector, s);
+    Frame3::from_parts(
+        Translation3::from(translation),
+        slerp(&start.rotation, &end.rotation, s),
+    )
+}
+
+/// Spherical interpolation of rotations, which for opposite rotations (where the direction of
+/// interpolation is undefined) turns about an arbitrary axis
+fn slerp(a: &UnitQuaternion<f64>, b: &UnitQuaternion<f64>, s: f64) -> UnitQuaternion<f64> {
+    a.try_slerp(b, s, 1e-12).unwrap_or_else(|| {
+        let axis = a * Vector3::x_axis();
+        a * UnitQuaternion::from_axis_angle(&axis, std::f64::consts::PI * s)
+    })
+}
+
+impl Display for FailureReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FailureReason::MissingPosition(r) => write!(f, "position {} is not defined", r),
+            FailureReason::UndefinedUFrame(n) => write!(f, "user frame {} is not defined", n),
+            FailureReason::UndefinedUTool(n) => write!(f, "tool frame {} is not defined", n),
+            FailureReason::FrameMismatch { position, active } => write!(
+                f,
+                "position recorded in UF {} UT {} but UF {} UT {} is active",
+                position.0, position.1, active.0, active.1
+            ),
+            FailureReason::Unreachable => write!(f, "position is not reachable"),
+            FailureReason::OutsideLimits(j) => write!(f, "joints {:?} are outside the limits", j),
+            FailureReason::ConfigurationChange => write!(f, "path requires a configuration change"),
+            FailureReason::InvalidArc => write!(f, "circular move has no unique arc"),
+            FailureReason::Collision { pairs, .. } => write!(f, "collision between {:?}", pairs),
+            FailureReason::Scene(e) => write!(f, "collision check failed: {}", e),
+        }
+    }
+}
+
+impl Display for SimulationFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fanuc::ls::{Speed, Termination};
+    use crate::test_helpers::cube;
+
+    const START: [f64; 6] = [0.0, 10.0, -20.0, 0.0, -70.0, 0.0];
+
+    fn program(robot: &Crx, moves: &[(MotionType, [f64; 6])]) -> LsProgram {
+        let mut program = LsProgram::new("TEST");
+        for (kind, joints) in moves {
+            let position = Position::cartesian(&robot.fk(joints), robot.config(joints));
+            program.add_move(
+                *kind,
+                position.with_frames(0, 0),
+                Speed::mm_per_sec(100.0),
+                Termination::Fine,
+            );
+        }
+        program
+    }
+
+    fn assert_joints(a: &[f64; 6], b: &[f64; 6]) {
+        for (x, y) in a.iter().zip(b.iter()) {
+            assert!((x - y).abs() < 1e-6, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn joint_and_linear_moves() {
+        let robot = Crx::new_5ia();
+        let a = [20.0, 15.0, -10.0, 10.0, -60.0, 30.0];
+        let b = [30.0, 20.0, -15.0, 10.0, -55.0, 30.0];
+        let program = program(&robot, &[(MotionType::Joint, a), (MotionType::Linear, b)]);
+
+        let result = Simulator::new(&robot).run(&program, &START);
+        assert!(result.succeeded(), "{:?}", result.failure);
+        assert_joints(&result.final_joints().unwrap(), &b);
+
+        // The linear move keeps the tool on a straight line between the two positions
+        let (p0, p1) = (robot.fk(&a), robot.fk(&b));
+        let line = (p1.translation.vector - p0.translation.vector).normalize();
+        for point in result.trajectory.iter().filter(|p| p.line == 2) {
+            let d = robot.fk(&point.joints).translation.vector - p0.translation.vector;
+            assert!((d - line * line.dot(&d)).norm() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn circular_move() {
+        let robot = Crx::new_5ia();
+        let a = [0.0, 20.0, -10.0, 0.0, -60.0, 0.0];
+        let mut program = program(&robot, &[(MotionType::Joint, a)]);
+
+        let pose = robot.fk(&a);
+        let config = robot.config(&a);
+        let shifted = |x: f64, y: f64| {
+            let frame = Frame3::translation(x, y, 0.0) * pose;
+            Position::cartesian(&frame, config).with_frames(0, 0)
+        };
+        program.add_circular(
+            shifted(50.0, 50.0),
+            shifted(100.0, 0.0),
+            Speed::mm_per_sec(100.0),
+            Termination::Fine,
+        );
+
+        let result = Simulator::new(&robot).run(&program, &START);
+        assert!(result.succeeded(), "{:?}", result.failure);
+
+        // Every point of the arc is 50 mm from its center
+        let center = pose.translation.vector + Vector3::new(50.0, 0.0, 0.0);
+        for point in result.trajectory.iter().filter(|p| p.line == 2) {
+            let p = robot.fk(&point.joints).translation.vector;
+            assert!(((p - center).norm() - 50.0).abs() < 1e-6);
+        }
+        let end = robot.fk(&result.final_joints().unwrap());
+        assert!((end.translation.vector - center - Vector3::new(50.0, 0.0, 0.0)).norm() < 1e-6);
+    }
+
+    #[test]
+    fn frames_and_registers() -> crate::Result<()> {
+        let robot = Crx::new_5ia();
+        let mut uframes = FrameTable::uframe();
+        uframes.set(2, Frame3::translation(400.0, 0.0, 0.0))?;
+        let mut utools = FrameTable::utool();
+        utools.set(1, Frame3::translation(0.0, 0.0, 100.0))?;
+
+        let joints = [10.0, 20.0, -10.0, 0.0, -60.0, 0.0];
+        let world = robot.clone().with_tool(utools.get(1).unwrap()).fk(&joints);
+        let local = uframes.get(2).unwrap().inverse() * world;
+
+        let mut program = LsProgram::new("FRAMES");
+        program.instructions.push(Instruction::UFrameNum(2));
+        program.instructions.push(Instruction::UToolNum(1));
+        program.instructions.push(Instruction::Motion(Motion {
+            kind: MotionType::Joint,
+            target: PositionRef::register(3),
+            via: None,
+            speed: Speed::percent(100.0),
+            termination: Termination::Fine,
+            options: Vec::new(),
+        }));
+
+        let register = Position::cartesian(&local, robot.config(&joints)).with_frames(2, 1);
+        let simulator = Simulator::new(&robot)
+            .with_uframes(uframes.clone())
+            .with_utools(utools.clone())
+            .with_register(3, register);
+        let result = simulator.run(&program, &START);
+        assert!(result.succeeded(), "{:?}", result.failure);
+        assert_joints(&result.final_joints().unwrap(), &joints);
+
+        // The register is interpreted in the active frames, whatever frames it was given with
+        let register = Position::cartesian(&local, robot.config(&joints)).with_frames(0, 0);
+        let result = Simulator::new(&robot)
+            .with_uframes(uframes)
+            .with_utools(utools)
+            .with_register(3, register)
+            .run(&program, &START);
+        assert!(result.succeeded(), "{:?}", result.failure);
+        assert_joints(&result.final_joints().unwrap(), &joints);
+
+        // Without the register the motion line fails
+        let result = Simulator::new(&robot).run(&program, &START);
+        assert_eq!(result.failed_line(), Some(3));
+        assert_eq!(
+            result.failure.unwrap().reason,
+            FailureReason::MissingPosition(PositionRef::register(3))
+        );
+
+        // Selecting a user frame beyond the end of the table fails on that line
+        let result = Simulator::new(&robot)
+            .with_uframes(FrameTable::new(1))
+            .run(&program, &START);
+        assert_eq!(result.failed_line(), Some(1));
+        assert_eq!(
+            result.failure.unwrap().reason,
+            FailureReason::UndefinedUFrame(2)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn reports_first_failing_line() {
+        let robot = Crx::new_5ia();
+        let a = [20.0, 15.0, -10.0, 10.0, -60.0, 30.0];
+        let mut program = program(&robot, &[(MotionType::Joint, a)]);
+
+        // A frame mismatch on line 3, after the active user frame is set on line 2
+        program.instructions.push(Instruction::UFrameNum(0));
+        let far = Frame3::translation(3000.0, 0.0, 0.0);
+        program.add_move(
+            MotionType::Linear,
+            Position::cartesian(&far, robot.config(&a)).with_frames(0, 0),
+            Speed::mm_per_sec(100.0),
+            Termination::Fine,
+        );
+        let result = Simulator::new(&robot).run(&program, &START);
+        assert_eq!(result.failed_line(), Some(3));
+        assert_eq!(result.failure.unwrap().reason, FailureReason::Unreachable);
+        assert!(result.trajectory.iter().all(|p| p.line == 1));
+
+        program.instructions[1] = Instruction::UToolNum(1);
+        let result = Simulator::new(&robot).run(&program, &START);
+        assert_eq!(
+            result.failure.unwrap().reason,
+            FailureReason::FrameMismatch {
+                position: (0, 0),
+                active: (0, 1)
+            }
+        );
+
+        program
+            .instructions
+            .insert(0, Instruction::Other("J P[9] 100% FINE".into()));
+        program.instructions[1] = Instruction::Motion(Motion {
+            kind: MotionType::Joint,
+            target: PositionRef::local(9),
+            via: None,
+            speed: Speed::percent(100.0),
+            termination: Termination::Fine,
+            options: Vec::new(),
+        });
+        let result = Simulator::new(&robot).run(&program, &START);
+        assert_eq!(result.failed_line(), Some(2));
+        assert_eq!(
+            result.failure.unwrap().to_string(),
+            "Line 2: position P[9] is not defined"
+        );
+    }
+
+    #[test]
+    fn collision_with_obstacle() {
+        let robot = Crx::new_5ia();
+        let a = [0.0, 20.0, -10.0, 0.0, -60.0, 0.0];
+        let b = [40.0, 20.0, -10.0, 0.0, -60.0, 0.0];
+        let program = program(&robot, &[(MotionType::Joint, a), (MotionType::Joint, b)]);
+
+        // Small boxes stand in for the link meshes, with every pair of links excluded so that
+        // only the obstacle can be hit
+        let mut scene = CollisionScene::new();
+        let links: [usize; 7] =
+            std::array::from_fn(|_| scene.add_interest(cube(Point3::origin(), 20.0)));
+        for i in 0..7 {
+            for j in i + 1..7 {
+                scene.add_exclusion(links[i], links[j]);
+            }
+        }
+
+        // An obstacle halfway along the flange's swing from a to b
+        let mid = robot.fk_all(&[20.0, 20.0, -10.0, 0.0, -60.0, 0.0])[5];
+        let obstacle = scene.add_background(cube(mid.translation.vector.into(), 30.0));
+
+        let clear = Simulator::new(&robot).run(&program, &START);
+        assert!(clear.succeeded());
+
+        let result = Simulator::new(&robot)
+            .with_scene(&scene, links)
+            .run(&program, &START);
+        let failure = result.failure.unwrap();
+        assert_eq!(failure.line, 2);
+        let FailureReason::Collision { joints, pairs } = failure.reason else {
+            panic!("Expected a collision");
+        };
+        assert_eq!(pairs, vec![(links[6], obstacle)]);
+        assert!(joints[0] > 0.0 && joints[0] < 20.0);
+    }
+
+    #[test]
+    fn scene_errors_fail_the_line() {
+        let robot = Crx::new_5ia();
+        let a = [0.0, 20.0, -10.0, 0.0, -60.0, 0.0];
+        let program = program(&robot, &[(MotionType::Joint, a)]);
+
+        // The link ids are never registered, so the scene can't place them
+        let mut scene = CollisionScene::new();
+        scene.add_background(cube(Point3::new(2000.0, 0.0, 0.0), 10.0));
+        let links = [100, 101, 102, 103, 104, 105, 106];
+
+        let result = Simulator::new(&robot)
+            .with_scene(&scene, links)
+            .run(&program, &START);
+        assert_eq!(result.failed_line(), Some(1));
+        assert!(result.trajectory.is_empty());
+        assert_eq!(
+            result.failure.unwrap().reason,
+            FailureReason::Scene("Mesh id 100 not found".to_string())
+        );
+    }
+}
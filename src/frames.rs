@@ -1,14 +1,39 @@
-use crate::nalgebra::{Matrix4, Translation3, UnitQuaternion};
+use crate::nalgebra::{Quaternion, Translation3, UnitQuaternion};
 use crate::type_aliases::{Frame3, Vector3};
 
-/// A struct representing a 6D pose in XYZ and WPR (Yaw, Pitch, Roll) format, commonly used by
-/// FANUC robots. Angles are represented in degrees.
+/// Below this value of the cosine of the middle Euler angle a rotation is treated as being in
+/// gimbal lock. This is about 6e-8 degrees away from +/-90.
+const GIMBAL_LOCK_TOL: f64 = 1e-9;
+
+/// A struct representing a 6D pose in XYZ and WPR format, commonly used by FANUC robots. Angles
+/// are represented in degrees.
+///
+/// W, P, and R are fixed-axis rotations about X, Y, and Z, applied in that order, so the
+/// rotation matrix is `Rz(R) * Ry(P) * Rx(W)`. When converting from a rotation the angles follow
+/// the same branch as the FANUC controller: P lies within [-90, 90] and W and R within
+/// (-180, 180].
+///
+/// At P = +/-90 (gimbal lock) only the difference (P = 90) or sum (P = -90) of W and R is
+/// defined. In that case W is set to zero and the whole angle is given to R, so that a pose in
+/// gimbal lock always converts to the same values. This rule is an assumption: it has not been
+/// checked against values read from a pendant, and the controller may split the angle between W
+/// and R differently. Either way the rotation is the same, but the displayed W and R may differ.
+///
+/// Before version 0.3.0 the `w` and `r` fields were swapped, with `w` about Z and `r` about X.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct XyzWpr {
     pub x: f64,
     pub y: f64,
     pub z: f64,
+
+    /// The rotation about the X axis in degrees
     pub w: f64,
+
+    /// The rotation about the Y axis in degrees
     pub p: f64,
+
+    /// The rotation about the Z axis in degrees
     pub r: f64,
 }
 
@@ -17,27 +42,17 @@ impl XyzWpr {
         XyzWpr { x, y, z, w, p, r }
     }
 
+    /// Convert a frame into XYZ and WPR values, matching the values displayed on the FANUC
+    /// controller. See the struct documentation for how gimbal lock is handled.
     pub fn from_isometry(isometry: &Frame3) -> Self {
-        let translation = isometry.translation.vector;
-        let rotation = isometry.rotation;
-        let (r, p, w) = rotation.euler_angles();
-        XyzWpr::new(
-            translation.x,
-            translation.y,
-            translation.z,
-            w.to_degrees(),
-            p.to_degrees(),
-            r.to_degrees(),
-        )
+        let t = isometry.translation.vector;
+        let (w, p, r) = fixed_xyz(&isometry.rotation);
+        XyzWpr::new(t.x, t.y, t.z, w, p, r)
     }
 
     pub fn to_isometry(&self) -> Frame3 {
         let translation = Vector3::new(self.x, self.y, self.z);
-        let rotation = UnitQuaternion::from_euler_angles(
-            self.r.to_radians(),
-            self.p.to_radians(),
-            self.w.to_radians(),
-        );
+        let rotation = from_fixed_xyz(self.w, self.p, self.r);
         Frame3::from_parts(Translation3::from(translation), rotation)
     }
 
@@ -49,6 +64,47 @@ impl XyzWpr {
     }
 }
 
+/// Extract the fixed-axis X, Y, Z angles in degrees of a rotation, `Rz(c) * Ry(b) * Rx(a)`,
+/// following the FANUC branch and gimbal lock conventions described on `XyzWpr`.
+///
+/// The gimbal lock branch (W = 0, with the whole angle given to R) is an assumed convention
+/// rather than one confirmed against the controller. Away from gimbal lock the angles are unique
+/// within the FANUC ranges, so only poses within `GIMBAL_LOCK_TOL` of P = +/-90 depend on it.
+fn fixed_xyz(rotation: &UnitQuaternion<f64>) -> (f64, f64, f64) {
+    let m = rotation.to_rotation_matrix().into_inner();
+
+    let cos_b = m[(0, 0)].hypot(m[(1, 0)]);
+    let b = (-m[(2, 0)]).atan2(cos_b);
+
+    let (a, c) = if cos_b > GIMBAL_LOCK_TOL {
+        (m[(2, 1)].atan2(m[(2, 2)]), m[(1, 0)].atan2(m[(0, 0)]))
+    } else if b > 0.0 {
+        // The second column is [sin(a - c), cos(a - c), 0]
+        (0.0, -m[(0, 1)].atan2(m[(1, 1)]))
+    } else {
+        // The second column is [-sin(a + c), cos(a + c), 0]
+        (0.0, (-m[(0, 1)]).atan2(m[(1, 1)]))
+    };
+
+    (
+        normalize(a.to_degrees()),
+        normalize(b.to_degrees()),
+        normalize(c.to_degrees()),
+    )
+}
+
+/// Build the rotation `Rz(c) * Ry(b) * Rx(a)` from angles in degrees
+fn from_fixed_xyz(a: f64, b: f64, c: f64) -> UnitQuaternion<f64> {
+    UnitQuaternion::from_euler_angles(a.to_radians(), b.to_radians(), c.to_radians())
+}
+
+/// Bring an angle in degrees into (-180, 180], also turning -0 into 0 so that values print the
+/// same way they do on the controller
+fn normalize(angle: f64) -> f64 {
+    let a = angle - 360.0 * ((angle - 180.0) / 360.0).ceil();
+    if a == 0.0 { 0.0 } else { a }
+}
+
 impl std::fmt::Display for XyzWpr {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(
@@ -59,11 +115,279 @@ impl std::fmt::Display for XyzWpr {
     }
 }
 
+/// A pose in the KUKA `XYZABC` format. A, B, and C are rotations in degrees about the Z, Y, and X
+/// axes, applied in that order as intrinsic rotations, so the rotation matrix is
+/// `Rz(A) * Ry(B) * Rx(C)`. This is the same rotation as FANUC WPR with A = R, B = P, and C = W,
+/// and it follows the same branch and gimbal lock conventions as `XyzWpr`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct XyzAbc {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+
+    /// The rotation about the Z axis in degrees
+    pub a: f64,
+
+    /// The rotation about the Y axis in degrees
+    pub b: f64,
+
+    /// The rotation about the X axis in degrees
+    pub c: f64,
+}
+
+impl XyzAbc {
+    pub fn new(x: f64, y: f64, z: f64, a: f64, b: f64, c: f64) -> Self {
+        XyzAbc { x, y, z, a, b, c }
+    }
+
+    pub fn from_isometry(isometry: &Frame3) -> Self {
+        let t = isometry.translation.vector;
+        let (c, b, a) = fixed_xyz(&isometry.rotation);
+        XyzAbc::new(t.x, t.y, t.z, a, b, c)
+    }
+
+    pub fn to_isometry(&self) -> Frame3 {
+        let translation = Vector3::new(self.x, self.y, self.z);
+        let rotation = from_fixed_xyz(self.c, self.b, self.a);
+        Frame3::from_parts(Translation3::from(translation), rotation)
+    }
+}
+
+impl std::fmt::Display for XyzAbc {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{{X {}, Y {}, Z {}, A {}, B {}, C {}}}",
+            self.x, self.y, self.z, self.a, self.b, self.c
+        )
+    }
+}
+
+/// A pose in the ABB `robtarget` format, with a position `[x, y, z]` and an orientation
+/// quaternion `[q1, q2, q3, q4]` where q1 is the scalar part. Converting from a frame always
+/// gives a quaternion with a non-negative q1, since the quaternion and its negative describe the
+/// same rotation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct XyzQuat {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+
+    /// The scalar part of the quaternion
+    pub q1: f64,
+    pub q2: f64,
+    pub q3: f64,
+    pub q4: f64,
+}
+
+impl XyzQuat {
+    pub fn new(x: f64, y: f64, z: f64, q1: f64, q2: f64, q3: f64, q4: f64) -> Self {
+        XyzQuat {
+            x,
+            y,
+            z,
+            q1,
+            q2,
+            q3,
+            q4,
+        }
+    }
+
+    pub fn from_isometry(isometry: &Frame3) -> Self {
+        let t = isometry.translation.vector;
+        let q = isometry.rotation.into_inner();
+        let q = if q.w < 0.0 { -q } else { q };
+        XyzQuat::new(t.x, t.y, t.z, q.w, q.i, q.j, q.k)
+    }
+
+    /// Convert to a frame. The quaternion is normalized first, so values which have been
+    /// rounded for display still produce a valid rotation.
+    pub fn to_isometry(&self) -> Frame3 {
+        let translation = Vector3::new(self.x, self.y, self.z);
+        let rotation =
+            UnitQuaternion::from_quaternion(Quaternion::new(self.q1, self.q2, self.q3, self.q4));
+        Frame3::from_parts(Translation3::from(translation), rotation)
+    }
+}
+
+impl std::fmt::Display for XyzQuat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "[[{},{},{}],[{},{},{},{}]]",
+            self.x, self.y, self.z, self.q1, self.q2, self.q3, self.q4
+        )
+    }
+}
+
+/// A pose in the Universal Robots format `p[x, y, z, rx, ry, rz]`. Unlike the other pose types
+/// the position is in meters, and the orientation is a rotation vector, whose direction is the
+/// axis of rotation and whose length is the angle in radians. Conversion to and from `Frame3`
+/// scales the position to and from millimeters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct XyzRotVec {
+    /// The X position in meters
+    pub x: f64,
+
+    /// The Y position in meters
+    pub y: f64,
+
+    /// The Z position in meters
+    pub z: f64,
+
+    pub rx: f64,
+    pub ry: f64,
+    pub rz: f64,
+}
+
+impl XyzRotVec {
+    pub fn new(x: f64, y: f64, z: f64, rx: f64, ry: f64, rz: f64) -> Self {
+        XyzRotVec {
+            x,
+            y,
+            z,
+            rx,
+            ry,
+            rz,
+        }
+    }
+
+    pub fn from_isometry(isometry: &Frame3) -> Self {
+        let t = isometry.translation.vector / 1000.0;
+        let r = isometry.rotation.scaled_axis();
+        XyzRotVec::new(t.x, t.y, t.z, r.x, r.y, r.z)
+    }
+
+    pub fn to_isometry(&self) -> Frame3 {
+        let translation = Vector3::new(self.x, self.y, self.z) * 1000.0;
+        let rotation = UnitQuaternion::from_scaled_axis(Vector3::new(self.rx, self.ry, self.rz));
+        Frame3::from_parts(Translation3::from(translation), rotation)
+    }
+}
+
+impl std::fmt::Display for XyzRotVec {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "p[{}, {}, {}, {}, {}, {}]",
+            self.x, self.y, self.z, self.rx, self.ry, self.rz
+        )
+    }
+}
+
+/// A pose in the Yaskawa Motoman format, with Rx, Ry, and Rz in degrees as fixed-axis rotations
+/// about X, Y, and Z, applied in that order. This is the same convention as FANUC WPR, and
+/// follows the same branch and gimbal lock conventions as `XyzWpr`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct XyzRxRyRz {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub rx: f64,
+    pub ry: f64,
+    pub rz: f64,
+}
+
+impl XyzRxRyRz {
+    pub fn new(x: f64, y: f64, z: f64, rx: f64, ry: f64, rz: f64) -> Self {
+        XyzRxRyRz {
+            x,
+            y,
+            z,
+            rx,
+            ry,
+            rz,
+        }
+    }
+
+    pub fn from_isometry(isometry: &Frame3) -> Self {
+        let t = isometry.translation.vector;
+        let (rx, ry, rz) = fixed_xyz(&isometry.rotation);
+        XyzRxRyRz::new(t.x, t.y, t.z, rx, ry, rz)
+    }
+
+    pub fn to_isometry(&self) -> Frame3 {
+        let translation = Vector3::new(self.x, self.y, self.z);
+        let rotation = from_fixed_xyz(self.rx, self.ry, self.rz);
+        Frame3::from_parts(Translation3::from(translation), rotation)
+    }
+}
+
+impl std::fmt::Display for XyzRxRyRz {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}, {}, {}, {}, {}, {}",
+            self.x, self.y, self.z, self.rx, self.ry, self.rz
+        )
+    }
+}
+
+/// A pose in the Staubli VAL3 `trsf` format. Rx, Ry, and Rz are rotations in degrees about the
+/// X axis, then the new Y axis, then the new Z axis, so the rotation matrix is
+/// `Rx(rx) * Ry(ry) * Rz(rz)`. When converting from a rotation Ry lies within [-90, 90] and Rx
+/// and Rz within (-180, 180]. At Ry = +/-90 (gimbal lock) Rx is set to zero and the whole angle
+/// is given to Rz.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Trsf {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub rx: f64,
+    pub ry: f64,
+    pub rz: f64,
+}
+
+impl Trsf {
+    pub fn new(x: f64, y: f64, z: f64, rx: f64, ry: f64, rz: f64) -> Self {
+        Trsf {
+            x,
+            y,
+            z,
+            rx,
+            ry,
+            rz,
+        }
+    }
+
+    pub fn from_isometry(isometry: &Frame3) -> Self {
+        // The inverse of Rx * Ry * Rz is Rz(-rz) * Ry(-ry) * Rx(-rx), which is a fixed XYZ
+        // rotation with the angles negated
+        let t = isometry.translation.vector;
+        let (a, b, c) = fixed_xyz(&isometry.rotation.inverse());
+        Trsf::new(t.x, t.y, t.z, normalize(-a), normalize(-b), normalize(-c))
+    }
+
+    pub fn to_isometry(&self) -> Frame3 {
+        let translation = Vector3::new(self.x, self.y, self.z);
+        let rotation = UnitQuaternion::from_axis_angle(&Vector3::x_axis(), self.rx.to_radians())
+            * UnitQuaternion::from_axis_angle(&Vector3::y_axis(), self.ry.to_radians())
+            * UnitQuaternion::from_axis_angle(&Vector3::z_axis(), self.rz.to_radians());
+        Frame3::from_parts(Translation3::from(translation), rotation)
+    }
+}
+
+impl std::fmt::Display for Trsf {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{{{}, {}, {}, {}, {}, {}}}",
+            self.x, self.y, self.z, self.rx, self.ry, self.rz
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::nalgebra::{try_convert, Matrix4};
+    use crate::nalgebra::{Matrix4, Rotation3};
     use approx::assert_relative_eq;
+    use std::f64::consts::PI;
     use test_case::test_case;
 
     #[test_case((-0.8156824504, -0.5743236360, -0.0693866068, -413.8635232282, 0.0004524620, 0.1193088953, -0.9928570807, 263.1863811434, 0.5784997281, -0.8098874913, -0.0970583124, 291.9820746748, 0.0000000000, 0.0000000000, 0.0000000000, 1.0000000000), (-413.8635232282, 263.1863811434, 291.9820746748, -96.8338333769, -35.3450905433, 179.9682178248))]
@@ -81,6 +405,7 @@ mod tests {
     #[test_case((-0.2037395422, 0.6790363200, -0.7052658187, -1.7434467919, -0.8508250650, -0.4791894336, -0.2155787453, -14.7633944063, -0.4843417261, 0.5561359212, 0.6753709570, -79.9344894886, 0.0000000000, 0.0000000000, 0.0000000000, 1.0000000000), (-1.7434467919, -14.7633944063, -79.9344894886, 39.4698348110, 28.9693530939, -103.4665369616))]
     #[test_case((0.7230637290, 0.1432361537, -0.6757678952, -49.7118498703, 0.5101470443, 0.5488670300, 0.6621895322, 20.3765636776, 0.4657561992, -0.8235462268, 0.3237943410, 8.4422995188, 0.0000000000, 0.0000000000, 0.0000000000, 1.0000000000), (-49.7118498703, 20.3765636776, 8.4422995188, -68.5366961539, -27.7591729450, 35.2043221840))]
     #[test_case((0.9572590453, -0.2498925755, 0.1456324858, 59.5794643289, -0.2886479285, -0.7934076632, 0.5358979878, 6.7548483372, -0.0183709982, -0.5550297115, -0.8316276365, -88.8341095469, 0.0000000000, 0.0000000000, 0.0000000000, 1.0000000000), (59.5794643289, 6.7548483372, -88.8341095469, -146.2807856754, 1.0526398756, -16.7799423973))]
+    #[allow(clippy::type_complexity)]
     fn xyzwpr_to_isometry(
         mf: (
             f64,
@@ -106,10 +431,21 @@ mod tests {
             mf.0, mf.1, mf.2, mf.3, mf.4, mf.5, mf.6, mf.7, mf.8, mf.9, mf.10, mf.11, mf.12, mf.13,
             mf.14, mf.15,
         );
-        let p = XyzWpr::new(pf.0, pf.1, pf.2, pf.5, pf.4, pf.3);
+        let p = XyzWpr::new(pf.0, pf.1, pf.2, pf.3, pf.4, pf.5);
         let t = p.to_isometry();
         let m_ = t.to_matrix();
         assert_relative_eq!(m, m_, epsilon = 1e-5);
+
+        // The controller's own matrix must convert back to the values it displayed
+        let rotation = Rotation3::from_matrix(&m.fixed_view::<3, 3>(0, 0).into_owned());
+        let frame = Frame3::from_parts(
+            Translation3::new(m[(0, 3)], m[(1, 3)], m[(2, 3)]),
+            UnitQuaternion::from_rotation_matrix(&rotation),
+        );
+        let p_ = XyzWpr::from_isometry(&frame);
+        assert_relative_eq!(p_.w, p.w, epsilon = 1e-5);
+        assert_relative_eq!(p_.p, p.p, epsilon = 1e-5);
+        assert_relative_eq!(p_.r, p.r, epsilon = 1e-5);
     }
 
     #[test_case((213.3455874397, -541.8828362001, -95.1324602951, -164.2348619634, 24.0489557219, -170.7519141710))]
@@ -124,7 +460,7 @@ mod tests {
     #[test_case((459.8055541953, 401.5061123248, 180.9052529976, 121.5295678361, -30.3457576176, 66.0945383908))]
     #[test_case((-68.9503102461, -79.4547936268, -62.0503091459, 121.1860503955, 5.5438249089, -33.3572843285))]
     fn xyzwpr_from_isometry(pf: (f64, f64, f64, f64, f64, f64)) {
-        let p = XyzWpr::new(pf.0, pf.1, pf.2, pf.5, pf.4, pf.3);
+        let p = XyzWpr::new(pf.0, pf.1, pf.2, pf.3, pf.4, pf.5);
         let i = p.to_isometry();
 
         let p_ = XyzWpr::from_isometry(&i);
@@ -136,4 +472,188 @@ mod tests {
         assert_relative_eq!(p.p, p_.p, epsilon = 1e-5);
         assert_relative_eq!(p.r, p_.r, epsilon = 1e-5);
     }
+
+    // These expected values come from the rotation math and the assumed gimbal lock rule on
+    // `XyzWpr`, not from pendant readings, so the cases at P = +/-90 check that rule is applied
+    // consistently rather than that it matches the controller
+    #[test_case((0.0, 0.0, 0.0), (0.0, 0.0, 0.0))]
+    #[test_case((180.0, 0.0, 0.0), (180.0, 0.0, 0.0))]
+    #[test_case((-180.0, 0.0, 0.0), (180.0, 0.0, 0.0); "negative w half turn")]
+    #[test_case((0.0, 0.0, -180.0), (0.0, 0.0, 180.0))]
+    #[test_case((30.0, 45.0, 400.0), (30.0, 45.0, 40.0))]
+    #[test_case((10.0, 100.0, 20.0), (-170.0, 80.0, -160.0))]
+    #[test_case((0.0, -180.0, 0.0), (180.0, 0.0, 180.0))]
+    #[test_case((0.0, 90.0, 30.0), (0.0, 90.0, 30.0))]
+    #[test_case((40.0, 90.0, 70.0), (0.0, 90.0, 30.0))]
+    #[test_case((-120.0, 90.0, 100.0), (0.0, 90.0, -140.0))]
+    #[test_case((40.0, -90.0, 70.0), (0.0, -90.0, 110.0))]
+    #[test_case((150.0, -90.0, 120.0), (0.0, -90.0, -90.0))]
+    #[test_case((180.0, 90.0, 0.0), (0.0, 90.0, 180.0))]
+    fn wpr_edge_cases(input: (f64, f64, f64), expected: (f64, f64, f64)) {
+        let p = XyzWpr::new(1.0, 2.0, 3.0, input.0, input.1, input.2);
+        let p_ = XyzWpr::from_isometry(&p.to_isometry());
+
+        assert_relative_eq!(p_.w, expected.0, epsilon = 1e-9);
+        assert_relative_eq!(p_.p, expected.1, epsilon = 1e-9);
+        assert_relative_eq!(p_.r, expected.2, epsilon = 1e-9);
+        assert!(p.approx_eq(&p_, 1e-12));
+    }
+
+    #[test]
+    fn no_negative_zero() {
+        let p = XyzWpr::from_isometry(&Frame3::identity());
+        assert_eq!(p.w.to_string(), "0");
+        assert_eq!(p.p.to_string(), "0");
+        assert_eq!(p.r.to_string(), "0");
+    }
+
+    #[test_case(90.0 - 1e-6)]
+    #[test_case(90.0 - 1e-8)]
+    #[test_case(-90.0 + 1e-8; "negative pitch")]
+    fn near_gimbal_lock(pitch: f64) {
+        // Close to the lock W and R are poorly conditioned individually, but the rotation they
+        // describe must still be reproduced
+        let p = XyzWpr::new(0.0, 0.0, 0.0, 25.0, pitch, -60.0);
+        let p_ = XyzWpr::from_isometry(&p.to_isometry());
+
+        assert!(p.approx_eq(&p_, 1e-9));
+        assert!(p_.p.abs() <= 90.0);
+    }
+
+    #[test_case((100.0, -200.0, 300.0, 0.0, 0.0, 0.0))]
+    #[test_case((100.0, -200.0, 300.0, -164.2348619634, 24.0489557219, -170.7519141710))]
+    #[test_case((-279.4908707038, 107.0379921066, 347.6153730196, 141.6846980303, 42.9511504078, 21.8352677126))]
+    #[test_case((17.8583707934, -103.5277608846, -41.9211021563, -129.0763419754, -64.3075695674, -119.6216242866))]
+    #[test_case((0.0, 0.0, 0.0, 180.0, 0.0, 180.0))]
+    #[test_case((0.0, 0.0, 0.0, 0.0, 90.0, 30.0))]
+    fn cross_conversions(pf: (f64, f64, f64, f64, f64, f64)) {
+        let wpr = XyzWpr::new(pf.0, pf.1, pf.2, pf.3, pf.4, pf.5);
+        let frame = wpr.to_isometry();
+
+        let kuka = XyzAbc::from_isometry(&frame);
+        let abb = XyzQuat::from_isometry(&frame);
+        let ur = XyzRotVec::from_isometry(&frame);
+        let yaskawa = XyzRxRyRz::from_isometry(&frame);
+        let staubli = Trsf::from_isometry(&frame);
+
+        for f in [
+            kuka.to_isometry(),
+            abb.to_isometry(),
+            ur.to_isometry(),
+            yaskawa.to_isometry(),
+            staubli.to_isometry(),
+        ] {
+            assert_relative_eq!(f, frame, epsilon = 1e-9);
+        }
+
+        // KUKA and Yaskawa angles are the FANUC angles in a different order
+        let fanuc = XyzWpr::from_isometry(&frame);
+        assert_eq!((kuka.a, kuka.b, kuka.c), (fanuc.r, fanuc.p, fanuc.w));
+        assert_eq!(
+            (yaskawa.rx, yaskawa.ry, yaskawa.rz),
+            (fanuc.w, fanuc.p, fanuc.r)
+        );
+        assert_relative_eq!(ur.x * 1000.0, wpr.x, epsilon = 1e-9);
+        assert!(abb.q1 >= 0.0);
+    }
+
+    #[test]
+    fn known_vendor_values() {
+        // The tool pointing down with its X axis along the world Y axis
+        let frame = Frame3::from_parts(
+            Translation3::new(500.0, 0.0, 250.0),
+            UnitQuaternion::from_euler_angles(PI, 0.0, PI / 2.0),
+        );
+
+        let kuka = XyzAbc::from_isometry(&frame);
+        assert_relative_eq!(kuka.a, 90.0, epsilon = 1e-9);
+        assert_relative_eq!(kuka.b, 0.0, epsilon = 1e-9);
+        assert_relative_eq!(kuka.c, 180.0, epsilon = 1e-9);
+
+        let abb = XyzQuat::from_isometry(&frame);
+        let h = 0.5_f64.sqrt();
+        assert_relative_eq!(abb.q1, 0.0, epsilon = 1e-12);
+        assert_relative_eq!(abb.q2.abs(), h, epsilon = 1e-12);
+        assert_relative_eq!(abb.q3.abs(), h, epsilon = 1e-12);
+        assert_relative_eq!(abb.q4, 0.0, epsilon = 1e-12);
+
+        let ur = XyzRotVec::from_isometry(&frame);
+        assert_relative_eq!(ur.x, 0.5, epsilon = 1e-12);
+        assert_relative_eq!(ur.z, 0.25, epsilon = 1e-12);
+        assert_relative_eq!(
+            Vector3::new(ur.rx, ur.ry, ur.rz).norm(),
+            PI,
+            epsilon = 1e-12
+        );
+
+        let staubli = Trsf::from_isometry(&frame);
+        assert_relative_eq!(staubli.rx, 180.0, epsilon = 1e-9);
+        assert_relative_eq!(staubli.ry, 0.0, epsilon = 1e-9);
+        assert_relative_eq!(staubli.rz, -90.0, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn staubli_intrinsic_order() {
+        let trsf = Trsf::new(0.0, 0.0, 0.0, 30.0, 40.0, 50.0);
+        let expected = UnitQuaternion::from_axis_angle(&Vector3::x_axis(), 30f64.to_radians())
+            * UnitQuaternion::from_axis_angle(&Vector3::y_axis(), 40f64.to_radians())
+            * UnitQuaternion::from_axis_angle(&Vector3::z_axis(), 50f64.to_radians());
+        assert_relative_eq!(trsf.to_isometry().rotation, expected, epsilon = 1e-12);
+
+        let back = Trsf::from_isometry(&trsf.to_isometry());
+        assert_relative_eq!(back.rx, 30.0, epsilon = 1e-9);
+        assert_relative_eq!(back.ry, 40.0, epsilon = 1e-9);
+        assert_relative_eq!(back.rz, 50.0, epsilon = 1e-9);
+
+        // In gimbal lock the whole angle goes to Rz
+        let locked = Trsf::from_isometry(&Trsf::new(0.0, 0.0, 0.0, 10.0, 90.0, 20.0).to_isometry());
+        assert_eq!(locked.rx, 0.0);
+        assert_relative_eq!(locked.ry, 90.0, epsilon = 1e-9);
+        assert_relative_eq!(locked.rz, 30.0, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn abb_quaternion_sign_and_rounding() {
+        // The negated quaternion is the same rotation, and rounded values are renormalized
+        let a = XyzQuat::new(1.0, 2.0, 3.0, -0.5, 0.5, -0.5, 0.5).to_isometry();
+        let b = XyzQuat::new(1.0, 2.0, 3.0, 0.50001, -0.5, 0.5, -0.5).to_isometry();
+        assert_relative_eq!(a, b, epsilon = 1e-4);
+
+        let q = XyzQuat::from_isometry(&a);
+        assert_relative_eq!(q.q1, 0.5, epsilon = 1e-12);
+        assert_relative_eq!(q.q2, -0.5, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn vendor_display() {
+        let frame = XyzWpr::new(1.0, 2.0, 3.0, 0.0, 0.0, 0.0).to_isometry();
+        assert_eq!(
+            XyzAbc::from_isometry(&frame).to_string(),
+            "{X 1, Y 2, Z 3, A 0, B 0, C 0}"
+        );
+        assert_eq!(
+            XyzQuat::from_isometry(&frame).to_string(),
+            "[[1,2,3],[1,0,0,0]]"
+        );
+        assert_eq!(
+            XyzRotVec::from_isometry(&frame).to_string(),
+            "p[0.001, 0.002, 0.003, 0, 0, 0]"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_field_names() {
+        let p = XyzWpr::new(1.0, 2.0, 3.0, 4.0, 5.0, 6.0);
+        let text = serde_json::to_string(&p).unwrap();
+        assert_eq!(text, r#"{"x":1.0,"y":2.0,"z":3.0,"w":4.0,"p":5.0,"r":6.0}"#);
+        assert_eq!(serde_json::from_str::<XyzWpr>(&text).unwrap(), p);
+
+        let q = XyzQuat::new(1.0, 2.0, 3.0, 1.0, 0.0, 0.0, 0.0);
+        let text = serde_json::to_string(&q).unwrap();
+        assert_eq!(
+            text,
+            r#"{"x":1.0,"y":2.0,"z":3.0,"q1":1.0,"q2":0.0,"q3":0.0,"q4":0.0}"#
+        );
+    }
 }
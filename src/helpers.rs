@@ -1,22 +1,11 @@
-use crate::Result;
-use crate::type_aliases::{Frame3, Vector3};
-use crate::nalgebra::{Matrix3, Translation3, UnitQuaternion, try_convert, Matrix4};
+#[cfg(test)]
+use crate::nalgebra::{Matrix4, try_convert};
+#[cfg(test)]
+use crate::{Frame3, Result};
 
-pub fn parts_to_iso(rot: Matrix3<f64>, trans: Vector3) -> Frame3 {
-    let r = UnitQuaternion::from_matrix(&rot);
-    let t = Translation3::from(trans);
-
-    Frame3::from_parts(t, r)
-}
-
-pub fn iso_to_parts(iso: &Frame3) -> (Matrix3<f64>, Vector3) {
-    let t = iso.translation.vector;
-    let r = iso.rotation.to_rotation_matrix();
-
-    (r.into(), t)
-}
-
-pub fn row_slice_to_iso(slice: &[f64]) -> Result<Frame3> {
+/// Build an isometry from the 16 values of a row-major homogeneous matrix
+#[cfg(test)]
+pub(crate) fn row_slice_to_iso(slice: &[f64]) -> Result<Frame3> {
     if slice.len() != 16 {
         return Err("Slice length must be 16".into());
     }
@@ -28,3 +17,100 @@ pub fn row_slice_to_iso(slice: &[f64]) -> Result<Frame3> {
 
     try_convert(m).ok_or("Failed to convert matrix to isometry".into())
 }
+
+/// Find a root of `f` inside the bracket `[a, b]` using Brent's method, where `fa` and `fb` are
+/// the already computed values at the ends of the bracket and have opposite signs. The root is
+/// polished until the bracket can no longer be reduced in floating point.
+pub fn brent(f: impl Fn(f64) -> f64, a: f64, b: f64, fa: f64, fb: f64) -> f64 {
+    let (mut a, mut b, mut fa, mut fb) = (a, b, fa, fb);
+    if fa.abs() < fb.abs() {
+        std::mem::swap(&mut a, &mut b);
+        std::mem::swap(&mut fa, &mut fb);
+    }
+
+    let mut c = a;
+    let mut fc = fa;
+    let mut d = b - a;
+    let mut bisected = true;
+
+    for _ in 0..200 {
+        if fb == 0.0 || (b - a).abs() <= 4.0 * f64::EPSILON * b.abs().max(1.0) {
+            break;
+        }
+
+        // Inverse quadratic interpolation when possible, otherwise the secant step
+        let mut s = if fa != fc && fb != fc {
+            a * fb * fc / ((fa - fb) * (fa - fc))
+                + b * fa * fc / ((fb - fa) * (fb - fc))
+                + c * fa * fb / ((fc - fa) * (fc - fb))
+        } else {
+            b - fb * (b - a) / (fb - fa)
+        };
+
+        // Fall back to bisection whenever the interpolated step is not making good progress
+        let mid = (3.0 * a + b) / 4.0;
+        let outside = (s - mid) * (s - b) >= 0.0;
+        let slow = if bisected {
+            (s - b).abs() >= (b - c).abs() / 2.0
+        } else {
+            (s - b).abs() >= (c - d).abs() / 2.0
+        };
+        bisected = outside || slow;
+        if bisected {
+            s = (a + b) / 2.0;
+        }
+
+        let fs = f(s);
+        d = c;
+        c = b;
+        fc = fb;
+
+        if fa.signum() != fs.signum() {
+            b = s;
+            fb = fs;
+        } else {
+            a = s;
+            fa = fs;
+        }
+
+        if fa.abs() < fb.abs() {
+            std::mem::swap(&mut a, &mut b);
+            std::mem::swap(&mut fa, &mut fb);
+        }
+    }
+
+    b
+}
+
+/// Find the location of the minimum of a unimodal function `f` on the interval `[a, b]` using
+/// golden section search, refined until the interval can no longer be reduced in floating point.
+pub fn golden_min(f: impl Fn(f64) -> f64, a: f64, b: f64) -> f64 {
+    let g = (5.0_f64.sqrt() - 1.0) / 2.0;
+    let (mut a, mut b) = (a, b);
+    let mut x1 = b - g * (b - a);
+    let mut x2 = a + g * (b - a);
+    let mut f1 = f(x1);
+    let mut f2 = f(x2);
+
+    for _ in 0..200 {
+        if (b - a).abs() <= 4.0 * f64::EPSILON * a.abs().max(1.0) {
+            break;
+        }
+
+        if f1 < f2 {
+            b = x2;
+            x2 = x1;
+            f2 = f1;
+            x1 = b - g * (b - a);
+            f1 = f(x1);
+        } else {
+            a = x1;
+            x1 = x2;
+            f1 = f2;
+            x2 = a + g * (b - a);
+            f2 = f(x2);
+        }
+    }
+
+    if f1 < f2 { x1 } else { x2 }
+}
@@ -4,6 +4,11 @@ mod helpers;
 mod type_aliases;
 pub mod micro_mesh;
 mod collision;
+mod robot;
+pub mod analysis;
+pub mod numeric_ik;
+#[cfg(test)]
+mod test_helpers;
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
@@ -12,6 +17,9 @@ pub use parry3d_f64;
 pub use parry3d_f64::na as nalgebra;
 
 // Re-export type aliases and pose types
-pub use frames::XyzWpr;
+pub use frames::{Trsf, XyzAbc, XyzQuat, XyzRotVec, XyzRxRyRz, XyzWpr};
 pub use type_aliases::*;
-pub use collision::{CollisionScene, TriMesh};
+pub use collision::{
+    AllowedCollisions, CollisionScene, LinkContact, PosedRobot, SweptContact, TriMesh,
+};
+pub use robot::{IkSolution, Robot, joint_distance};
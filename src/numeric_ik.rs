@@ -0,0 +1,269 @@
+//! An iterative damped least-squares (Levenberg-Marquardt) inverse kinematics solver which works
+//! with any robot implementing the `Robot` trait. It is slower than the analytic solvers and only
+//! finds the single solution nearest its seed, but it keeps working where they can't, such as
+//! with non-nominal link parameters, unusual tool frames, or targets sitting on a singularity.
+
+use crate::nalgebra::{Matrix6, Vector6};
+use crate::{Frame3, IkSolution, Robot};
+
+/// Options which control the numeric IK solver
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumericIkOptions {
+    /// The maximum number of iterations before giving up
+    pub max_iterations: usize,
+
+    /// The position error (mm) at or below which the solver has converged
+    pub position_tolerance: f64,
+
+    /// The orientation error (radians) at or below which the solver has converged
+    pub orientation_tolerance: f64,
+
+    /// The initial damping factor. Larger values take smaller, safer steps, while smaller values
+    /// converge faster when the seed is already close.
+    pub damping: f64,
+
+    /// A length (mm) used to make position errors comparable with orientation errors. A position
+    /// error of this length is weighted the same as an orientation error of one radian.
+    pub length_scale: f64,
+}
+
+impl Default for NumericIkOptions {
+    fn default() -> Self {
+        Self {
+            max_iterations: 200,
+            position_tolerance: 1e-6,
+            orientation_tolerance: 1e-9,
+            damping: 1e-3,
+            length_scale: 500.0,
+        }
+    }
+}
+
+/// How the numeric IK solver finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumericIkStatus {
+    /// The target was reached within the tolerances
+    Converged,
+
+    /// The iteration limit was reached before the target was
+    MaxIterations,
+
+    /// No step could reduce the error any further, which usually means the target is out of
+    /// reach or the joint limits are in the way
+    Stalled,
+}
+
+/// The result of a numeric IK solve
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumericIkResult {
+    /// The final joint angles along with their residuals against the target. If the solver did
+    /// not converge these are the closest joint angles it found.
+    pub solution: IkSolution,
+
+    /// How the solver finished
+    pub status: NumericIkStatus,
+
+    /// The number of iterations used
+    pub iterations: usize,
+}
+
+impl NumericIkResult {
+    /// Check if the solver reached the target
+    pub fn converged(&self) -> bool {
+        self.status == NumericIkStatus::Converged
+    }
+}
+
+/// Solve the inverse kinematics of a robot numerically, starting from a seed and moving towards
+/// the target with damped least-squares steps. The damping adapts each iteration, shrinking while
+/// steps reduce the error and growing when they don't. Every step is clamped to the robot's joint
+/// limits, so the result always lies within them.
+///
+/// # Arguments
+///
+/// * `robot`: the robot to solve for
+/// * `target`: the desired position and orientation of the robot's tool frame
+/// * `seed`: the joint angles in degrees to start from, which determines which solution is found
+/// * `options`: the solver options
+///
+/// returns: NumericIkResult
+///
+/// # Examples
+///
+/// ```
+/// use industrial_robots::fanuc::Crx;
+/// use industrial_robots::numeric_ik::{solve, NumericIkOptions};
+///
+/// let robot = Crx::new_5ia();
+/// let target = robot.fk(&[10.0, 20.0, -30.0, 40.0, 50.0, 60.0]);
+/// let result = solve(&robot, &target, &[0.0, 0.0, 0.0, 0.0, 45.0, 0.0], &NumericIkOptions::default());
+/// assert!(result.converged());
+/// ```
+pub fn solve<R: Robot + ?Sized>(
+    robot: &R,
+    target: &Frame3,
+    seed: &[f64; 6],
+    options: &NumericIkOptions,
+) -> NumericIkResult {
+    let limits = robot.joint_limits();
+    let mut joints = clamp(seed, &limits);
+    let mut error = pose_error(robot, &joints, target, options.length_scale);
+    let mut damping = options.damping;
+
+    for iteration in 0..options.max_iterations {
+        let solution = IkSolution::verify(robot, &joints, target);
+        if solution.within((options.position_tolerance, options.orientation_tolerance)) {
+            return NumericIkResult {
+                solution,
+                status: NumericIkStatus::Converged,
+                iterations: iteration,
+            };
+        }
+
+        let mut jac = robot.jacobian(&joints);
+        jac.fixed_view_mut::<3, 6>(0, 0)
+            .unscale_mut(options.length_scale);
+        let jt = jac.transpose();
+        let jtj = jt * jac;
+        let gradient = jt * error;
+
+        // Try increasingly damped steps until one of them reduces the error
+        let mut improved = false;
+        while damping < 1e10 {
+            let lhs = jtj + Matrix6::from_diagonal(&jtj.diagonal().add_scalar(1e-12)) * damping;
+            let Some(step) = lhs.cholesky().map(|c| c.solve(&gradient)) else {
+                damping *= 10.0;
+                continue;
+            };
+
+            let mut candidate = joints;
+            for (c, s) in candidate.iter_mut().zip(step.iter()) {
+                *c += s.to_degrees();
+            }
+            let candidate = clamp(&candidate, &limits);
+            let candidate_error = pose_error(robot, &candidate, target, options.length_scale);
+
+            if candidate_error.norm() < error.norm() {
+                joints = candidate;
+                error = candidate_error;
+                damping = (damping / 10.0).max(1e-12);
+                improved = true;
+                break;
+            }
+
+            damping *= 10.0;
+        }
+
+        if !improved {
+            return NumericIkResult {
+                solution: IkSolution::verify(robot, &joints, target),
+                status: NumericIkStatus::Stalled,
+                iterations: iteration + 1,
+            };
+        }
+    }
+
+    let solution = IkSolution::verify(robot, &joints, target);
+    let status = if solution.within((options.position_tolerance, options.orientation_tolerance)) {
+        NumericIkStatus::Converged
+    } else {
+        NumericIkStatus::MaxIterations
+    };
+
+    NumericIkResult {
+        solution,
+        status,
+        iterations: options.max_iterations,
+    }
+}
+
+/// The twist which would move the tool from its position at `joints` to the target, with the
+/// linear part divided by the length scale
+fn pose_error<R: Robot + ?Sized>(
+    robot: &R,
+    joints: &[f64; 6],
+    target: &Frame3,
+    length_scale: f64,
+) -> Vector6<f64> {
+    let current = robot.fk(joints);
+    let linear = (target.translation.vector - current.translation.vector) / length_scale;
+    let angular = (target.rotation * current.rotation.inverse()).scaled_axis();
+    Vector6::new(
+        linear.x, linear.y, linear.z, angular.x, angular.y, angular.z,
+    )
+}
+
+fn clamp(joints: &[f64; 6], limits: &[(f64, f64); 6]) -> [f64; 6] {
+    let mut result = *joints;
+    for (j, (lower, upper)) in result.iter_mut().zip(limits.iter()) {
+        *j = j.clamp(*lower, *upper);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fanuc::{Crx, LrMate};
+
+    #[test]
+    fn crx_from_nearby_seed() {
+        let robot = Crx::new_5ia();
+        let joints = [10.0, -20.0, 30.0, -40.0, 50.0, -60.0];
+        let target = robot.fk(&joints);
+        let seed = [15.0, -15.0, 25.0, -30.0, 45.0, -70.0];
+
+        let result = solve(&robot, &target, &seed, &NumericIkOptions::default());
+        assert!(result.converged());
+        for (a, b) in result.solution.joints.iter().zip(joints.iter()) {
+            assert!((a - b).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn crx_calibrated_dimensions() {
+        let robot = Crx::from_dimensions(185.0, 410.3, 429.6, 145.2, 130.4).unwrap();
+        let target = robot.fk(&[-60.0, 30.0, -10.0, 100.0, -80.0, 20.0]);
+        let seed = [-50.0, 20.0, 0.0, 90.0, -70.0, 0.0];
+
+        let result = solve(&robot, &target, &seed, &NumericIkOptions::default());
+        assert!(result.converged());
+        assert!(result.solution.within((1e-6, 1e-9)));
+    }
+
+    #[test]
+    fn lr_mate_on_wrist_singularity() {
+        // J5 at zero leaves J4 and J6 aligned, but the damping keeps the steps finite
+        let robot = LrMate::new_200id();
+        let target = robot.fk(&[0.0, 10.0, -10.0, 20.0, 0.0, 20.0]);
+        let seed = [5.0, 0.0, 0.0, 0.0, 10.0, 0.0];
+
+        let result = solve(&robot, &target, &seed, &NumericIkOptions::default());
+        assert!(result.converged());
+        assert!(result.solution.within((1e-6, 1e-9)));
+    }
+
+    #[test]
+    fn unreachable_target() {
+        let robot = Crx::new_5ia();
+        let target = Frame3::translation(5000.0, 0.0, 0.0);
+        let result = solve(&robot, &target, &[0.0; 6], &NumericIkOptions::default());
+
+        assert!(!result.converged());
+        assert!(robot.within_limits(&result.solution.joints));
+    }
+
+    #[test]
+    fn respects_joint_limits() -> crate::Result<()> {
+        let joints = [10.0, -20.0, 30.0, -40.0, 50.0, -60.0];
+        let mut limits = Crx::new_5ia().joint_limits();
+        limits[0] = (-5.0, 5.0);
+        let robot = Crx::new_5ia().with_joint_limits(limits)?;
+        let target = robot.fk(&joints);
+
+        let result = solve(&robot, &target, &[0.0; 6], &NumericIkOptions::default());
+        assert!(!result.converged());
+        assert!(robot.within_limits(&result.solution.joints));
+        Ok(())
+    }
+}
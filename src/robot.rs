@@ -0,0 +1,272 @@
+//! Common kinematic interface for 6-axis robot arms. Tools which only need forward kinematics,
+//! inverse kinematics, and basic layout information (collision checking, planners, reachability
+//! studies) should be written against the `Robot` trait rather than a specific model, so that
+//! they work with every arm in the crate.
+
+use crate::nalgebra::{Matrix3, Matrix6};
+use crate::{Frame3, Vector3};
+
+/// A single inverse kinematics solution, along with how closely the forward kinematics of the
+/// solution actually reproduce the target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IkSolution {
+    /// The joint angles in degrees, in the controller convention
+    pub joints: [f64; 6],
+
+    /// The distance between the target position and the position reached by the joints
+    pub position_error: f64,
+
+    /// The angle in radians between the target orientation and the orientation reached by the
+    /// joints
+    pub orientation_error: f64,
+}
+
+impl IkSolution {
+    /// Run the forward kinematics on a set of joint angles and measure the residuals against
+    /// the target frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `robot`: the robot used to compute the forward kinematics
+    /// * `joints`: the joint angles in degrees
+    /// * `target`: the frame the joint angles are supposed to reach
+    ///
+    /// returns: IkSolution
+    pub fn verify<R: Robot + ?Sized>(robot: &R, joints: &[f64; 6], target: &Frame3) -> Self {
+        let reached = robot.fk(joints);
+        let position_error = (reached.translation.vector - target.translation.vector).norm();
+        let orientation_error = reached.rotation.angle_to(&target.rotation);
+
+        Self {
+            joints: *joints,
+            position_error,
+            orientation_error,
+        }
+    }
+
+    /// Check if both residuals are within a `(position, orientation)` tolerance, where the
+    /// orientation tolerance is in radians.
+    pub fn within(&self, tolerance: (f64, f64)) -> bool {
+        self.position_error <= tolerance.0 && self.orientation_error <= tolerance.1
+    }
+}
+
+/// A 6-axis robot arm with known forward and inverse kinematics.
+///
+/// Joint values are always exchanged in degrees and in the convention of the robot's own
+/// controller, so any vendor specific quirks (like the FANUC J2/J3 interaction) are handled by
+/// the implementation and never leak out to the caller.
+pub trait Robot {
+    /// Compute the position and orientation of the robot's tool frame in relation to the world
+    /// for a set of joint angles in degrees. If the robot has no tool set this is the flange, and
+    /// if it has no base set the world is the robot origin.
+    fn fk(&self, joints: &[f64; 6]) -> Frame3;
+
+    /// Compute the full kinematic chain for a set of joint angles in degrees. The array holds
+    /// one frame per joint in relation to the world, and the final frame is the flange, which
+    /// does not include the tool.
+    fn fk_all(&self, joints: &[f64; 6]) -> [Frame3; 6];
+
+    /// Compute the joint angles in degrees which place the robot's tool frame at the target
+    /// frame, given in relation to the world. An empty vector means the target is not reachable.
+    fn ik(&self, target: &Frame3) -> Vec<[f64; 6]>;
+
+    /// The tool frame in relation to the flange, which is the identity if no tool is set
+    fn tool(&self) -> Frame3 {
+        Frame3::identity()
+    }
+
+    /// Compute the forward kinematics with a different tool frame than the one set on the
+    /// robot, such as when switching between UTOOL numbers in a program.
+    ///
+    /// # Arguments
+    ///
+    /// * `joints`: the joint angles in degrees
+    /// * `tool`: the tool frame in relation to the flange
+    ///
+    /// returns: Isometry<f64, Unit<Quaternion<f64>>, 3>
+    fn fk_with_tool(&self, joints: &[f64; 6], tool: &Frame3) -> Frame3 {
+        self.fk_all(joints)[5] * tool
+    }
+
+    /// Compute the inverse kinematics for a target given for a different tool frame than the
+    /// one set on the robot.
+    ///
+    /// # Arguments
+    ///
+    /// * `target`: the desired position and orientation of the tool frame
+    /// * `tool`: the tool frame in relation to the flange
+    ///
+    /// returns: Vec<[f64; 6], Global>
+    fn ik_with_tool(&self, target: &Frame3, tool: &Frame3) -> Vec<[f64; 6]> {
+        self.ik(&(target * tool.inverse() * self.tool()))
+    }
+
+    /// The pose of the robot origin in the world, which is the identity if no base is set
+    fn base(&self) -> Frame3 {
+        Frame3::identity()
+    }
+
+    /// The pose of the bottom of the robot's mounting flange in the world, which sits `z0`
+    /// below the robot origin
+    fn mount(&self) -> Frame3 {
+        self.base() * Frame3::translation(0.0, 0.0, -self.z0())
+    }
+
+    /// Compute the forward kinematics with the result expressed in a user frame (the FANUC
+    /// UFRAME) rather than in the world, which is how the controller displays and records
+    /// Cartesian positions.
+    ///
+    /// # Arguments
+    ///
+    /// * `joints`: the joint angles in degrees
+    /// * `user_frame`: the user frame in relation to the world
+    ///
+    /// returns: Isometry<f64, Unit<Quaternion<f64>>, 3>
+    fn fk_in(&self, joints: &[f64; 6], user_frame: &Frame3) -> Frame3 {
+        user_frame.inverse() * self.fk(joints)
+    }
+
+    /// Compute the inverse kinematics for a target expressed in a user frame (the FANUC UFRAME)
+    /// rather than in the world.
+    ///
+    /// # Arguments
+    ///
+    /// * `target`: the desired position and orientation of the tool frame in the user frame
+    /// * `user_frame`: the user frame in relation to the world
+    ///
+    /// returns: Vec<[f64; 6], Global>
+    fn ik_in(&self, target: &Frame3, user_frame: &Frame3) -> Vec<[f64; 6]> {
+        self.ik(&(user_frame * target))
+    }
+
+    /// Compute the 6x6 geometric Jacobian for a set of joint angles in degrees. It maps joint
+    /// rates in radians per second (in the controller convention) to the twist of the tool frame
+    /// in the world frame, with the linear velocity in the top three rows and the angular
+    /// velocity in the bottom three.
+    fn jacobian(&self, joints: &[f64; 6]) -> Matrix6<f64>;
+
+    /// Compute the analytic Jacobian for a set of joint angles in degrees, which maps joint
+    /// rates in radians per second to the rates of change of the tool's X, Y, Z, W, P, and R
+    /// values as the FANUC controller defines them (W, P, and R are fixed rotations about the X,
+    /// Y, and Z axes, applied in that order). The angular rows are in radians per second.
+    ///
+    /// The WPR rates are undefined when P is at +/-90 degrees, in which case `None` is returned.
+    fn jacobian_wpr(&self, joints: &[f64; 6]) -> Option<Matrix6<f64>> {
+        let (_, p, r) = self.fk(joints).rotation.euler_angles();
+        if p.cos().abs() < 1e-9 {
+            return None;
+        }
+
+        // The columns map the W, P, and R rates to the angular velocity they produce
+        let e = Matrix3::from_columns(&[
+            Vector3::new(r.cos() * p.cos(), r.sin() * p.cos(), -p.sin()),
+            Vector3::new(-r.sin(), r.cos(), 0.0),
+            Vector3::z(),
+        ]);
+
+        let mut jac = self.jacobian(joints);
+        let angular = e.try_inverse()? * jac.fixed_view::<3, 6>(3, 0);
+        jac.fixed_view_mut::<3, 6>(3, 0).copy_from(&angular);
+        Some(jac)
+    }
+
+    /// Convert joint angles in degrees, in the controller convention, to the angle in radians of
+    /// each link about its joint axis in the kinematic chain. These only differ by units for most
+    /// robots, but not where the controller couples joints together, such as J2 and J3 on FANUC
+    /// arms. The conversion is linear, so it also converts a change in joint angles.
+    fn kinematic_angles(&self, joints: &[f64; 6]) -> [f64; 6] {
+        joints.map(f64::to_radians)
+    }
+
+    /// The number of joints in the robot
+    fn joint_count(&self) -> usize {
+        6
+    }
+
+    /// The lower and upper limits of each joint in degrees, in the controller convention
+    fn joint_limits(&self) -> [(f64, f64); 6];
+
+    /// The height from the bottom of the mounting flange to the robot origin. This doesn't
+    /// affect the kinematics, but is necessary for doing layout and position studies.
+    fn z0(&self) -> f64;
+
+    /// Compute the inverse kinematics for a target frame and verify each solution by running it
+    /// back through the forward kinematics, reporting the position and orientation residuals.
+    ///
+    /// # Arguments
+    ///
+    /// * `target`: the desired position and orientation of the robot's tool frame
+    /// * `tolerance`: if provided, a `(position, orientation)` tolerance, with the orientation
+    ///   in radians, and any solution with a residual above it will be discarded
+    ///
+    /// returns: Vec<IkSolution, Global>
+    fn ik_verified(&self, target: &Frame3, tolerance: Option<(f64, f64)>) -> Vec<IkSolution> {
+        self.ik(target)
+            .iter()
+            .map(|j| IkSolution::verify(self, j, target))
+            .filter(|s| tolerance.is_none_or(|t| s.within(t)))
+            .collect()
+    }
+
+    /// Compute the inverse kinematics for a target frame and sort the solutions by their
+    /// weighted joint-space distance from a seed, closest first. When following a path, using
+    /// the current joint state as the seed and taking the first solution keeps the motion from
+    /// jumping between configurations.
+    ///
+    /// Multi-turn joints are compared by their actual values rather than modulo 360 degrees,
+    /// so a solution which requires a joint to unwind by a full turn is correctly treated as
+    /// being far from the seed.
+    ///
+    /// # Arguments
+    ///
+    /// * `target`: the desired position and orientation of the robot's tool frame
+    /// * `seed`: the joint angles in degrees to measure the distance from
+    /// * `weights`: if provided, the weight applied to each joint's squared difference, for
+    ///   example to make moving the large base joints more expensive than moving the wrist.
+    ///   Otherwise all joints are weighted equally.
+    ///
+    /// returns: Vec<[f64; 6], Global>
+    fn ik_closest(
+        &self,
+        target: &Frame3,
+        seed: &[f64; 6],
+        weights: Option<&[f64; 6]>,
+    ) -> Vec<[f64; 6]> {
+        let weights = weights.copied().unwrap_or([1.0; 6]);
+        let mut solutions = self
+            .ik(target)
+            .into_iter()
+            .map(|j| (joint_distance(&j, seed, &weights), j))
+            .collect::<Vec<_>>();
+
+        solutions.sort_by(|a, b| a.0.total_cmp(&b.0));
+        solutions.into_iter().map(|(_, j)| j).collect()
+    }
+
+    /// Check whether every joint in a set of joint angles is within the robot's limits
+    fn within_limits(&self, joints: &[f64; 6]) -> bool {
+        self.joint_limits()
+            .iter()
+            .zip(joints.iter())
+            .all(|((lower, upper), j)| j >= lower && j <= upper)
+    }
+}
+
+/// The weighted Euclidean distance between two sets of joint angles, in degrees.
+///
+/// # Arguments
+///
+/// * `a`: the first set of joint angles in degrees
+/// * `b`: the second set of joint angles in degrees
+/// * `weights`: the weight applied to the squared difference of each joint
+///
+/// returns: f64
+pub fn joint_distance(a: &[f64; 6], b: &[f64; 6], weights: &[f64; 6]) -> f64 {
+    a.iter()
+        .zip(b.iter())
+        .zip(weights.iter())
+        .map(|((a, b), w)| w * (a - b).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
@@ -0,0 +1,53 @@
+//! Fixtures and checks shared by the unit tests of several modules.
+
+use crate::{Point3, Robot, TriMesh, Vector3};
+use approx::assert_relative_eq;
+use parry3d_f64::bounding_volume::Aabb;
+
+/// Check if two sets of joint angles in degrees are the same, treating angles a whole turn
+/// apart as equal
+pub fn same_joints(a: &[f64; 6], b: &[f64; 6]) -> bool {
+    a.iter()
+        .zip(b.iter())
+        .all(|(x, y)| ((x - y + 180.0).rem_euclid(360.0) - 180.0).abs() < 1e-6)
+}
+
+/// Check the geometric Jacobian of a robot against the change in its forward kinematics for a
+/// small step of each joint
+pub fn assert_jacobian_matches_fk(robot: &impl Robot, joints: &[f64; 6]) {
+    let jac = robot.jacobian(joints);
+    let base = robot.fk(joints);
+
+    let step: f64 = 1e-6;
+    for i in 0..6 {
+        let mut moved = *joints;
+        moved[i] += step.to_degrees();
+        let f = robot.fk(&moved);
+
+        let linear = (f.translation.vector - base.translation.vector) / step;
+        let angular = (f.rotation * base.rotation.inverse()).scaled_axis() / step;
+
+        assert_relative_eq!(
+            jac.fixed_view::<3, 1>(0, i).clone_owned(),
+            linear,
+            epsilon = 1e-3
+        );
+        assert_relative_eq!(
+            jac.fixed_view::<3, 1>(3, i).clone_owned(),
+            angular,
+            epsilon = 1e-6
+        );
+    }
+}
+
+/// A triangle mesh of an axis aligned box between two corners
+pub fn box_mesh(min: Point3, max: Point3) -> TriMesh {
+    let (v, f) = Aabb::new(min, max).to_trimesh();
+    TriMesh::new(v, f).unwrap()
+}
+
+/// A triangle mesh of an axis aligned cube around a center point, given its half size
+pub fn cube(center: Point3, half: f64) -> TriMesh {
+    let half = Vector3::new(half, half, half);
+    box_mesh(center - half, center + half)
+}